@@ -0,0 +1,180 @@
+use crate::Monkey;
+
+// Where an item is at the start of a round: the monkey holding it and its worry level.
+type State = (usize, u64);
+
+// One round for a single item. Items do not interact, so it is inspected by the monkey holding it
+// and then by every monkey it is thrown to later in the same round. Inspections are added to
+// `counts`.
+fn item_round(monkeys: &[Monkey], modulus: u64, (mut monkey, mut worry): State, counts: &mut [u64]) -> State {
+    loop {
+        counts[monkey] += 1;
+        worry = monkeys[monkey].operation.apply_modulo(worry, modulus);
+        let target = monkeys[monkey].target(worry);
+        if target < monkey {
+            return (target, worry);
+        }
+        monkey = target;
+    }
+}
+
+// Rounds before the item enters its cycle, and length of the cycle, found with Brent's algorithm
+// so that memory does not grow with the cycle.
+pub fn find_cycle(monkeys: &[Monkey], modulus: u64, start: State) -> (u64, u64) {
+    let mut counts = vec![0; monkeys.len()];
+    let mut next = |state: State| item_round(monkeys, modulus, state, &mut counts);
+
+    let (mut power, mut length) = (1, 1);
+    let mut tortoise = start;
+    let mut hare = next(start);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare;
+            power *= 2;
+            length = 0;
+        }
+        hare = next(hare);
+        length += 1;
+    }
+
+    let (mut tortoise, mut hare) = (start, start);
+    for _ in 0..length {
+        hare = next(hare);
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = next(tortoise);
+        hare = next(hare);
+        tail += 1;
+    }
+    (tail, length)
+}
+
+// Runs `rounds` rounds for one item, returns where it ends.
+fn play_item(monkeys: &[Monkey], modulus: u64, mut state: State, rounds: u64, counts: &mut [u64]) -> State {
+    for _ in 0..rounds {
+        state = item_round(monkeys, modulus, state, counts);
+    }
+    state
+}
+
+// Inspections of every monkey after `rounds` rounds with worry kept modulo `modulus`, in a time
+// that depends on the cycles of the items and not on `rounds`. An error when an operation cannot be
+// computed modulo `modulus`.
+pub fn counts_after_rounds(monkeys: &[Monkey], modulus: u64, rounds: u64) -> Result<Vec<u64>, String> {
+    if let Some(id) = monkeys.iter().position(|monkey| !monkey.operation.is_modular(modulus)) {
+        return Err(format!("Monkey {}: operation cannot be computed modulo {}", id, modulus));
+    }
+    let mut counts = vec![0; monkeys.len()];
+    for (id, monkey) in monkeys.iter().enumerate() {
+        for item in &monkey.items {
+            let start = (id, item % modulus);
+            let (tail, length) = find_cycle(monkeys, modulus, start);
+            let state = play_item(monkeys, modulus, start, rounds.min(tail), &mut counts);
+            if rounds <= tail {
+                continue;
+            }
+            let (cycles, remaining) = ((rounds - tail) / length, (rounds - tail) % length);
+            let mut cycle_counts = vec![0; monkeys.len()];
+            play_item(monkeys, modulus, state, length, &mut cycle_counts);
+            for (count, cycle_count) in counts.iter_mut().zip(&cycle_counts) {
+                *count += cycles * cycle_count;
+            }
+            play_item(monkeys, modulus, state, remaining, &mut counts);
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cycles::{counts_after_rounds, find_cycle, play_item},
+        parser::parse_monkeys,
+        play_round,
+        tests::EXAMPLE,
+        Monkey, Relief,
+    };
+
+    const MODULUS: u64 = 23 * 19 * 13 * 17;
+
+    #[test]
+    fn same_counts_as_playing_rounds() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let mut played = monkeys.clone();
+        for round in 1..=1000 {
+            play_round(&mut played, Relief::Modulo(MODULUS), None).unwrap();
+            if [1, 20, 37, 500, 1000].contains(&round) {
+                let expected = played.iter().map(|monkey| monkey.processed as u64).collect::<Vec<u64>>();
+                assert_eq!(counts_after_rounds(&monkeys, MODULUS, round).unwrap(), expected, "after round {}", round);
+            }
+        }
+    }
+
+    #[test]
+    fn counts_of_example() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+
+        assert_eq!(counts_after_rounds(&monkeys, MODULUS, 10_000).unwrap(), vec![52166, 47830, 1938, 52013]);
+        assert_eq!(counts_after_rounds(&monkeys, MODULUS, 0).unwrap(), vec![0; 4]);
+        let monkeys = parse_monkeys(&EXAMPLE.replace("new = old + 3", "new = old % 7")).unwrap();
+        assert_eq!(counts_after_rounds(&monkeys, MODULUS, 10), Err("Monkey 3: operation cannot be computed modulo 96577".to_string()));
+    }
+
+    #[test]
+    fn cycles_repeat() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let (tail, length) = find_cycle(&monkeys, MODULUS, (0, 79));
+        let mut played = monkeys.clone();
+        played.iter_mut().for_each(|monkey| monkey.items.clear());
+        played[0].items.push(79);
+        let mut states = vec![];
+        for _ in 0..tail + 2 * length {
+            states.push(played.iter().position(|monkey| !monkey.items.is_empty()).map(|id| (id, played[id].items[0])).unwrap());
+            play_round(&mut played, Relief::Modulo(MODULUS), None).unwrap();
+        }
+
+        assert!(length > 0);
+        assert_eq!(states[tail as usize], states[(tail + length) as usize]);
+        assert!((0..tail as usize).all(|round| states[round] != states[round + length as usize]));
+    }
+
+    // Every item played round after round, without looking for cycles.
+    fn played_counts(monkeys: &[Monkey], rounds: u64) -> Vec<u64> {
+        let mut counts = vec![0; monkeys.len()];
+        for (id, monkey) in monkeys.iter().enumerate() {
+            for item in &monkey.items {
+                play_item(monkeys, MODULUS, (id, item % MODULUS), rounds, &mut counts);
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn huge_round_counts() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+        // Once every item is in its cycle, all of them are back where they were after a common
+        // multiple of the lengths of their cycles.
+        let cycles = monkeys.iter().enumerate().flat_map(|(id, monkey)| monkey.items.iter().map(move |item| (id, *item))).map(|start| find_cycle(&monkeys, MODULUS, start)).collect::<Vec<(u64, u64)>>();
+        let tail = cycles.iter().map(|(tail, _)| *tail).max().unwrap();
+        let length = cycles.iter().fold(1, |length, (_, cycle)| length / gcd(length, *cycle) * cycle);
+        let rounds = 1_000_000_000_000;
+        let (repeats, remaining) = ((rounds - tail) / length, (rounds - tail) % length);
+
+        let before = played_counts(&monkeys, tail);
+        let after_one = played_counts(&monkeys, tail + length);
+        let expected = played_counts(&monkeys, tail + remaining).iter().zip(before.iter().zip(&after_one)).map(|(count, (before, after))| count + repeats * (after - before)).collect::<Vec<u64>>();
+
+        assert_eq!((tail, length), (175, 76608));
+        assert_eq!(counts_after_rounds(&monkeys, MODULUS, rounds).unwrap(), expected);
+        assert_eq!(counts_after_rounds(&monkeys, MODULUS, tail + length).unwrap(), after_one);
+    }
+
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+}
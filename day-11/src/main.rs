@@ -1,11 +1,18 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
+use std::{env, fs};
+
+use cycles::counts_after_rounds;
+use operation::Operation;
+use parser::parse_monkeys;
+use trace::{holdings, inspection_counts, narrate_turn, Event};
 
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelRefIterator;
 
+mod cycles;
+mod operation;
+mod parser;
+mod trace;
+
 #[derive(Debug, Clone)]
 struct Monkey {
     items: Vec<u64>,
@@ -16,52 +23,79 @@ struct Monkey {
     processed: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Relief {
+    // Worry is divided by 3 after each inspection.
+    Bored,
+    // Worry is only kept modulo the product of the divisors.
+    Modulo(u64),
+}
+
 impl Monkey {
-    fn process_items_with_decreasing_worryness(&mut self) -> Vec<(u64, usize)> {
-        self.processed += self.items.len();
-        let result = self
-            .items
-            .par_iter()
-            .map(|item| {
-                let new_item: u64 = self.apply_operation(*item) / 3;
-                if new_item % self.divisibility == 0 {
-                    (new_item, self.monkey_true)
-                } else {
-                    (new_item, self.monkey_false)
-                }
-            })
-            .collect::<Vec<(u64, usize)>>();
-        self.items = vec![];
-        result
+    fn process_items_with_decreasing_worryness(&mut self, trace: Option<&mut Vec<Event>>) -> Result<Vec<(u64, usize)>, String> {
+        self.process_items(Relief::Bored, trace)
     }
 
     fn process_items_without_decreasing_worryness(
         &mut self,
         stress_relief: u64,
-    ) -> Vec<(u64, usize)> {
-        self.processed += self.items.len();
-        let result = self
+        trace: Option<&mut Vec<Event>>,
+    ) -> Result<Vec<(u64, usize)>, String> {
+        self.process_items(Relief::Modulo(stress_relief), trace)
+    }
+
+    // Worry levels after the operation and after relief, and where the item goes.
+    fn inspect(&self, item: u64, relief: Relief) -> Result<(u64, u64, usize), String> {
+        let (operated, new_item) = match relief {
+            Relief::Bored => {
+                let operated = self.apply_operation(item)?;
+                (operated, operated / 3)
+            },
+            Relief::Modulo(modulus) => {
+                if !self.operation.is_modular(modulus) {
+                    return Err(format!("operation cannot be computed modulo {}", modulus));
+                }
+                let operated = self.operation.apply_modulo(item, modulus);
+                (operated, operated)
+            },
+        };
+        Ok((operated, new_item, self.target(new_item)))
+    }
+
+    fn target(&self, worry: u64) -> usize {
+        if worry.is_multiple_of(self.divisibility) {
+            self.monkey_true
+        } else {
+            self.monkey_false
+        }
+    }
+
+    // `trace` gets what happens to each item, in order. Nothing changes when the worry of an item
+    // cannot be computed.
+    fn process_items(&mut self, relief: Relief, trace: Option<&mut Vec<Event>>) -> Result<Vec<(u64, usize)>, String> {
+        let inspections = self
             .items
             .par_iter()
-            .map(|item| {
-                let new_item: u64 = self.apply_operation(*item) % stress_relief;
-                if new_item % self.divisibility == 0 {
-                    (new_item, self.monkey_true)
-                } else {
-                    (new_item, self.monkey_false)
+            .map(|item| self.inspect(*item, relief))
+            .collect::<Result<Vec<(u64, u64, usize)>, String>>()?;
+        self.processed += self.items.len();
+        if let Some(trace) = trace {
+            for (item, (operated, new_item, monkey)) in self.items.iter().zip(&inspections) {
+                trace.push(Event::Inspect { worry: *item });
+                trace.push(Event::Operate { worry: *operated, relief });
+                if relief == Relief::Bored {
+                    trace.push(Event::Bored { worry: *new_item });
                 }
-            })
-            .collect::<Vec<(u64, usize)>>();
+                trace.push(Event::Test { divisibility: self.divisibility, divisible: new_item.is_multiple_of(self.divisibility) });
+                trace.push(Event::Throw { worry: *new_item, monkey: *monkey });
+            }
+        }
         self.items = vec![];
-        result
+        Ok(inspections.into_iter().map(|(_, new_item, monkey)| (new_item, monkey)).collect())
     }
 
-    fn apply_operation(&self, number: u64) -> u64 {
-        match self.operation {
-            Operation::Add(value) => number + value,
-            Operation::Multiply(value) => number * value,
-            Operation::Square => number * number,
-        }
+    fn apply_operation(&self, number: u64) -> Result<u64, String> {
+        self.operation.apply(number)
     }
 
     fn pass(&mut self, number: u64) {
@@ -69,26 +103,10 @@ impl Monkey {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-enum Operation {
-    Add(u64),
-    Multiply(u64),
-    Square,
-}
-
 impl From<String> for Operation {
     fn from(input: String) -> Self {
-        let split = input.split(" ").collect::<Vec<&str>>();
-        if split.len() == 8 {
-            match (split[6], split[7]) {
-                ("+", value) => Operation::Add(value.parse::<u64>().unwrap()),
-                ("*", "old") => Operation::Square,
-                ("*", value) => Operation::Multiply(value.parse::<u64>().unwrap()),
-                _ => unreachable!(),
-            }
-        } else {
-            unreachable!()
-        }
+        let expression = input.trim().trim_start_matches("Operation:");
+        Operation::parse(expression).unwrap()
     }
 }
 
@@ -104,7 +122,7 @@ impl MonkeyBuilder {
     fn new() -> Self {
         MonkeyBuilder {
             items: vec![],
-            operation: Operation::Add(0),
+            operation: Operation::Old,
             divisibility: 1,
             monkey_true: 0,
             monkey_false: 0,
@@ -148,76 +166,107 @@ impl MonkeyBuilder {
     }
 }
 
-fn main() {
-    let file_name = "input/day-11";
-    let file = File::open(file_name).expect("oops");
-    let reader = BufReader::new(file);
-
-    let mut monkeys: Vec<Monkey> = vec![];
-    let mut builder = MonkeyBuilder::new();
-    for line in reader.lines() {
-        let line = line.unwrap();
-        if line.starts_with("Monkey") {
-            // ignore this line
-        } else if line.starts_with("  Starting items") {
-            line[18..].split(",").for_each(|item| {
-                builder.add_item(item.trim().parse::<u64>().unwrap());
-            });
-        } else if line.starts_with("  Operation") {
-            builder.set_operation(Operation::from(line));
-        } else if line.starts_with("  Test") {
-            builder.set_divisibility(line[21..].parse::<u64>().unwrap());
-        } else if line.starts_with("    If true") {
-            builder.set_monkey_true(line[29..].parse::<usize>().unwrap());
-        } else if line.starts_with("    If false") {
-            builder.set_monkey_false(line[30..].parse::<usize>().unwrap());
-        } else {
-            monkeys.push(builder.build());
-            builder = MonkeyBuilder::new();
+// Every monkey takes its turn. `trace` gets the events of each turn, with the monkey number. Stops
+// at the first monkey that cannot compute the worry of an item.
+fn play_round(monkeys: &mut [Monkey], relief: Relief, mut trace: Option<&mut Vec<(usize, Vec<Event>)>>) -> Result<(), String> {
+    for m in 0..monkeys.len() {
+        let mut current_monkey = monkeys[m].clone();
+        let mut events = vec![];
+        let events_trace = trace.is_some().then_some(&mut events);
+        let new_items = match relief {
+            Relief::Bored => current_monkey.process_items_with_decreasing_worryness(events_trace),
+            Relief::Modulo(stress_relief) => current_monkey.process_items_without_decreasing_worryness(stress_relief, events_trace),
+        }
+        .map_err(|error| format!("Monkey {}: {}", m, error))?;
+        for (item, monkey) in new_items {
+            monkeys[monkey].pass(item);
+        }
+        monkeys[m] = current_monkey;
+        if let Some(trace) = trace.as_mut() {
+            trace.push((m, events));
         }
     }
-    monkeys.push(builder.build());
-    let mut monkeys_step2 = monkeys.clone();
+    Ok(())
+}
 
-    for _step in 0..20 {
-        for m in 0..monkeys.len() {
-            let mut current_monkey = monkeys[m].clone();
-            let new_items = current_monkey.process_items_with_decreasing_worryness();
-            for (item, monkey) in new_items {
-                monkeys[monkey].pass(item);
+// Narrates `rounds` rounds like the puzzle does, with what the monkeys hold, or how many items they
+// inspected with `Relief::Modulo`, after the rounds in `reports`.
+fn trace(monkeys: &mut [Monkey], rounds: usize, relief: Relief, reports: &[usize]) -> Result<(), String> {
+    for round in 1..=rounds {
+        let mut turns = vec![];
+        play_round(monkeys, relief, Some(&mut turns))?;
+        for (m, events) in &turns {
+            println!("{}", narrate_turn(*m, &monkeys[*m], events).join("\n"));
+        }
+        if reports.is_empty() || reports.contains(&round) {
+            println!();
+            match relief {
+                Relief::Bored => {
+                    println!("After round {}, the monkeys are holding items with these worry levels:", round);
+                    println!("{}", holdings(monkeys).join("\n"));
+                },
+                Relief::Modulo(_) => {
+                    println!("== After round {} ==", round);
+                    println!("{}", inspection_counts(monkeys).join("\n"));
+                },
             }
-            monkeys[m] = current_monkey;
+            println!();
         }
     }
+    Ok(())
+}
 
-    let mut processed_numbers = monkeys
-        .iter()
-        .map(|monkey| monkey.processed)
-        .collect::<Vec<usize>>();
-    processed_numbers.sort();
-    processed_numbers.reverse();
-
-    println!("{:?}", processed_numbers[0] * processed_numbers[1]);
+fn main() {
+    let file_name = "input/day-11";
+    let content = fs::read_to_string(file_name).expect("oops");
+    let mut monkeys = match parse_monkeys(&content) {
+        Ok(monkeys) => monkeys,
+        Err(error) => {
+            println!("{}: {}", file_name, error);
+            return;
+        },
+    };
+    let monkeys_step2 = monkeys.clone();
 
     let stress_relief = monkeys_step2
         .iter()
-        .map(|monkey| monkey.divisibility.clone())
+        .map(|monkey| monkey.divisibility)
         .reduce(|accum, item| accum * item)
         .unwrap();
 
-    for _step in 0..10_000 {
-        for m in 0..monkeys_step2.len() {
-            let mut current_monkey = monkeys_step2[m].clone();
-            let new_items =
-                current_monkey.process_items_without_decreasing_worryness(stress_relief);
-            for (item, monkey) in new_items {
-                monkeys_step2[monkey].pass(item);
-            }
-            monkeys_step2[m] = current_monkey;
+    if env::args().nth(1).as_deref() == Some("trace") {
+        let rounds = env::args().nth(2).map_or(1, |rounds| rounds.parse().unwrap());
+        let relief = if env::args().nth(3).as_deref() == Some("modulo") { Relief::Modulo(stress_relief) } else { Relief::Bored };
+        let reports = env::args().nth(4).map_or(vec![], |reports| reports.split(',').map(|round| round.parse().unwrap()).collect());
+        if let Err(error) = trace(&mut monkeys, rounds, relief, &reports) {
+            println!("{}", error);
         }
+        return;
     }
 
-    let mut processed_numbers = monkeys_step2
+    if env::args().nth(1).as_deref() == Some("rounds") {
+        let rounds = env::args().nth(2).map_or(10_000, |rounds| rounds.parse().unwrap());
+        let mut counts = match counts_after_rounds(&monkeys, stress_relief, rounds) {
+            Ok(counts) => counts,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            },
+        };
+        counts.sort();
+        counts.reverse();
+        println!("{:?}", counts[0] as u128 * counts[1] as u128);
+        return;
+    }
+
+    for _step in 0..20 {
+        if let Err(error) = play_round(&mut monkeys, Relief::Bored, None) {
+            println!("{}", error);
+            return;
+        }
+    }
+
+    let mut processed_numbers = monkeys
         .iter()
         .map(|monkey| monkey.processed)
         .collect::<Vec<usize>>();
@@ -225,18 +274,59 @@ fn main() {
     processed_numbers.reverse();
 
     println!("{:?}", processed_numbers[0] * processed_numbers[1]);
+
+    let mut counts = match counts_after_rounds(&monkeys_step2, stress_relief, 10_000) {
+        Ok(counts) => counts,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        },
+    };
+    counts.sort();
+    counts.reverse();
+
+    println!("{:?}", counts[0] * counts[1]);
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Operation;
+    use crate::{operation::Operator, parser::parse_monkeys, play_round, Operation, Relief};
+
+    pub const EXAMPLE: &str = "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+";
 
     #[test]
     fn parse_operation_multiply() {
         let input = "  Operation: new = old * 19".to_string();
         let operation = Operation::from(input);
 
-        assert_eq!(operation, Operation::Multiply(19));
+        assert_eq!(operation, Operation::Binary(Operator::Multiply, Box::new(Operation::Old), Box::new(Operation::Constant(19))));
     }
 
     #[test]
@@ -244,7 +334,7 @@ mod tests {
         let input = "  Operation: new = old * old".to_string();
         let operation = Operation::from(input);
 
-        assert_eq!(operation, Operation::Square);
+        assert_eq!(operation, Operation::Binary(Operator::Multiply, Box::new(Operation::Old), Box::new(Operation::Old)));
     }
 
     #[test]
@@ -252,6 +342,22 @@ mod tests {
         let input = "  Operation: new = old + 25".to_string();
         let operation = Operation::from(input);
 
-        assert_eq!(operation, Operation::Add(25));
+        assert_eq!(operation, Operation::Binary(Operator::Add, Box::new(Operation::Old), Box::new(Operation::Constant(25))));
+    }
+
+    #[test]
+    fn stop_when_worry_cannot_be_computed() {
+        let mut monkeys = parse_monkeys(&EXAMPLE.replace("new = old + 6", "new = old - 60")).unwrap();
+
+        assert_eq!(play_round(&mut monkeys, Relief::Bored, None), Err("Monkey 1: cannot compute 54 - 60".to_string()));
+        assert_eq!(monkeys[1].items, vec![54, 65, 75, 74]);
+        assert_eq!(monkeys[1].processed, 0);
+
+        for operation in ["new = old - 4", "new = old % 7", "new = old / 2"] {
+            let mut monkeys = parse_monkeys(&EXAMPLE.replace("new = old + 6", operation)).unwrap();
+
+            assert_eq!(play_round(&mut monkeys, Relief::Modulo(96577), None), Err("Monkey 1: operation cannot be computed modulo 96577".to_string()), "{}", operation);
+            assert_eq!(monkeys[1].items, vec![54, 65, 75, 74]);
+        }
     }
 }
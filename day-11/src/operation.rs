@@ -0,0 +1,303 @@
+use std::fmt;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl Operator {
+    fn symbol(self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Subtract => '-',
+            Operator::Multiply => '*',
+            Operator::Divide => '/',
+            Operator::Remainder => '%',
+        }
+    }
+
+    fn precedence(self) -> usize {
+        match self {
+            Operator::Add | Operator::Subtract => 0,
+            Operator::Multiply | Operator::Divide | Operator::Remainder => 1,
+        }
+    }
+}
+
+// Right-hand side of `new = ...`.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Operation {
+    Old,
+    Constant(u64),
+    Binary(Operator, Box<Operation>, Box<Operation>),
+}
+
+impl Operation {
+    // Whether this needs parentheses as an operand of `parent`, on its right side if `right`.
+    fn needs_parentheses(&self, parent: Operator, right: bool) -> bool {
+        match self {
+            Operation::Binary(operator, _, _) => operator.precedence() < parent.precedence() || (right && operator.precedence() == parent.precedence()),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operation::Old => write!(f, "old"),
+            Operation::Constant(value) => write!(f, "{}", value),
+            Operation::Binary(operator, left, right) => {
+                let side = |operand: &Operation, right: bool| {
+                    if operand.needs_parentheses(*operator, right) {
+                        format!("({})", operand)
+                    } else {
+                        operand.to_string()
+                    }
+                };
+                write!(f, "{} {} {}", side(left, false), operator.symbol(), side(right, true))
+            },
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Token {
+    Old,
+    Number(u64),
+    Operator(Operator),
+    Open,
+    Close,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '0'..='9' => {
+                let mut number = c.to_string();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    number.push(digit);
+                }
+                Token::Number(number.parse().map_err(|_| format!("`{}` is too large", number))?)
+            },
+            'a'..='z' => {
+                let mut word = c.to_string();
+                while let Some(letter) = chars.next_if(char::is_ascii_lowercase) {
+                    word.push(letter);
+                }
+                if word != "old" {
+                    return Err(format!("unknown name `{}`", word));
+                }
+                Token::Old
+            },
+            _ => match [Operator::Add, Operator::Subtract, Operator::Multiply, Operator::Divide, Operator::Remainder].into_iter().find(|operator| operator.symbol() == c) {
+                Some(operator) => Token::Operator(operator),
+                None => return Err(format!("unknown operator `{}`", c)),
+            },
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// Recursive descent, `*`, `/` and `%` bind tighter than `+` and `-`, all of them to the left.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next_operator(&mut self, operators: &[Operator]) -> Option<Operator> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                self.position += 1;
+                Some(*operator)
+            },
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Operation, String> {
+        let mut left = self.product()?;
+        while let Some(operator) = self.next_operator(&[Operator::Add, Operator::Subtract]) {
+            left = Operation::Binary(operator, Box::new(left), Box::new(self.product()?));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Operation, String> {
+        let mut left = self.operand()?;
+        while let Some(operator) = self.next_operator(&[Operator::Multiply, Operator::Divide, Operator::Remainder]) {
+            let right = self.operand()?;
+            if operator != Operator::Multiply && right == Operation::Constant(0) {
+                return Err(format!("`{}` by 0", operator.symbol()));
+            }
+            left = Operation::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Operation, String> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        match token {
+            Some(Token::Old) => Ok(Operation::Old),
+            Some(Token::Number(value)) => Ok(Operation::Constant(value)),
+            Some(Token::Open) => {
+                let inner = self.sum()?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(inner)
+                    },
+                    _ => Err("missing `)`".to_string()),
+                }
+            },
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("expression ends too early".to_string()),
+        }
+    }
+}
+
+impl Operation {
+    // The part after `Operation:`, e.g. `new = old * 19`.
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let expression = expression.trim().strip_prefix("new").and_then(|rest| rest.trim_start().strip_prefix('=')).ok_or("expected `new = <expression>`")?;
+        let mut parser = Parser { tokens: tokenize(expression)?, position: 0 };
+        let operation = parser.sum()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(operation),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+
+    // Worry is never negative: an error when it would go below 0 or get too large, or when
+    // dividing by 0.
+    pub fn apply(&self, old: u64) -> Result<u64, String> {
+        match self {
+            Operation::Old => Ok(old),
+            Operation::Constant(value) => Ok(*value),
+            Operation::Binary(operator, left, right) => {
+                let (left, right) = (left.apply(old)?, right.apply(old)?);
+                let result = match operator {
+                    Operator::Add => left.checked_add(right),
+                    Operator::Subtract => left.checked_sub(right),
+                    Operator::Multiply => left.checked_mul(right),
+                    Operator::Divide => left.checked_div(right),
+                    Operator::Remainder => left.checked_rem(right),
+                };
+                result.ok_or_else(|| format!("cannot compute {} {} {}", left, operator.symbol(), right))
+            },
+        }
+    }
+
+    // Whether the result modulo `modulus` only depends on `old` modulo `modulus`, and can be
+    // computed without failing: true for sums and products, and for remainders by a divisor of
+    // `modulus`. Divisions never are, nor differences as whether worry goes below 0 depends on more
+    // than `old` modulo `modulus`.
+    pub fn is_modular(&self, modulus: u64) -> bool {
+        match self {
+            Operation::Old | Operation::Constant(_) => true,
+            Operation::Binary(Operator::Divide | Operator::Subtract, _, _) => false,
+            Operation::Binary(Operator::Remainder, left, right) => match **right {
+                Operation::Constant(divisor) => divisor != 0 && modulus.is_multiple_of(divisor) && left.is_modular(modulus),
+                _ => false,
+            },
+            Operation::Binary(_, left, right) => left.is_modular(modulus) && right.is_modular(modulus),
+        }
+    }
+
+    // Same as `apply` followed by `% modulus` but without getting large, only for operations where
+    // `is_modular` holds.
+    pub fn apply_modulo(&self, old: u64, modulus: u64) -> u64 {
+        match self {
+            Operation::Old => old % modulus,
+            Operation::Constant(value) => value % modulus,
+            Operation::Binary(operator, left, right) => {
+                let left = left.apply_modulo(old, modulus) as u128;
+                // The divisor of a remainder can be `modulus` itself.
+                let right = match (operator, &**right) {
+                    (Operator::Remainder, Operation::Constant(divisor)) => *divisor as u128,
+                    _ => right.apply_modulo(old, modulus) as u128,
+                };
+                let result = match operator {
+                    Operator::Add => left + right,
+                    Operator::Multiply => left * right,
+                    Operator::Remainder => left % right,
+                    Operator::Subtract | Operator::Divide => unreachable!("Differences and divisions do not work modulo"),
+                };
+                (result % modulus as u128) as u64
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operation::{Operation, Operator};
+
+    fn binary(operator: Operator, left: Operation, right: Operation) -> Operation {
+        Operation::Binary(operator, Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn parse_with_precedence_and_parentheses() {
+        let operation = Operation::parse("new = (old + 3) * old % 7 - 2").unwrap();
+
+        assert_eq!(
+            operation,
+            binary(
+                Operator::Subtract,
+                binary(Operator::Remainder, binary(Operator::Multiply, binary(Operator::Add, Operation::Old, Operation::Constant(3)), Operation::Old), Operation::Constant(7)),
+                Operation::Constant(2)
+            )
+        );
+        assert_eq!(operation.apply(5), Ok(3));
+        assert_eq!(operation.to_string(), "(old + 3) * old % 7 - 2");
+        assert_eq!(Operation::parse("new = old - (2 - old) * (old / 2)").unwrap().to_string(), "old - (2 - old) * (old / 2)");
+        assert_eq!(Operation::parse("new=old/2/3").unwrap().apply(30), Ok(5));
+    }
+
+    #[test]
+    fn reject_invalid_expressions() {
+        assert_eq!(Operation::parse("new = old ^ 2"), Err("unknown operator `^`".to_string()));
+        assert_eq!(Operation::parse("new = older + 2"), Err("unknown name `older`".to_string()));
+        assert_eq!(Operation::parse("new = (old + 2"), Err("missing `)`".to_string()));
+        assert_eq!(Operation::parse("new = old +"), Err("expression ends too early".to_string()));
+        assert_eq!(Operation::parse("new = old 2"), Err("unexpected Number(2)".to_string()));
+        assert_eq!(Operation::parse("new = old % 0"), Err("`%` by 0".to_string()));
+        assert_eq!(Operation::parse("old * 2"), Err("expected `new = <expression>`".to_string()));
+    }
+
+    #[test]
+    fn report_worry_that_cannot_be_computed() {
+        assert_eq!(Operation::parse("new = old - 10").unwrap().apply(3), Err("cannot compute 3 - 10".to_string()));
+        assert_eq!(Operation::parse("new = old - 10").unwrap().apply(13), Ok(3));
+        assert_eq!(Operation::parse("new = old % (old - old)").unwrap().apply(4), Err("cannot compute 4 % 0".to_string()));
+        assert_eq!(Operation::parse("new = old * old").unwrap().apply(u64::MAX), Err(format!("cannot compute {} * {}", u64::MAX, u64::MAX)));
+    }
+
+    #[test]
+    fn modular_operations() {
+        let modulus = 23 * 19 * 13 * 17;
+        for expression in ["new = old * old + 7", "new = (old + 3) * 19 + 4", "new = old % 13 * old", "new = 5 * 2 + old", "new = old % 96577 * 2"] {
+            let operation = Operation::parse(expression).unwrap();
+            assert!(operation.is_modular(modulus), "{}", expression);
+            for old in [0, 1, 12, 96577, 1_000_003] {
+                assert_eq!(operation.apply_modulo(old, modulus), operation.apply(old).unwrap() % modulus, "{} with {}", expression, old);
+            }
+        }
+        for expression in ["new = old / 2", "new = old - 4", "new = 5 - 2 + old", "new = old % 7", "new = old % old", "new = (old + 1) % 11 * 2"] {
+            assert!(!Operation::parse(expression).unwrap().is_modular(modulus), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+use crate::{Monkey, MonkeyBuilder, Operation};
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    // 1-based, as shown by editors.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError { line, message: message.into() })
+}
+
+fn number<T: std::str::FromStr>(text: &str, line: usize) -> Result<T, ParseError> {
+    text.parse().or_else(|_| error(line, format!("`{}` is not a number", text)))
+}
+
+// Fields of one monkey with the line they were found on.
+#[derive(Default)]
+struct Notes {
+    header: usize,
+    items: Option<(usize, Vec<u64>)>,
+    operation: Option<(usize, Operation)>,
+    divisibility: Option<(usize, u64)>,
+    monkey_true: Option<(usize, usize)>,
+    monkey_false: Option<(usize, usize)>,
+}
+
+fn set<T>(field: &mut Option<(usize, T)>, value: T, line: usize, name: &str) -> Result<(), ParseError> {
+    if let Some((previous, _)) = field {
+        return error(line, format!("{} already given on line {}", name, previous));
+    }
+    *field = Some((line, value));
+    Ok(())
+}
+
+// `words` must start with `expected`, returns the word after it.
+fn last_word<'a>(words: &[&'a str], expected: &[&str], line: usize) -> Result<&'a str, ParseError> {
+    match words.split_last() {
+        Some((last, start)) if start == expected => Ok(last),
+        _ => error(line, format!("expected `{} <number>`", expected.join(" "))),
+    }
+}
+
+impl Notes {
+    fn read(&mut self, text: &str, line: usize) -> Result<(), ParseError> {
+        let (field, value) = text.split_once(':').ok_or_else(|| ParseError { line, message: format!("unexpected line `{}`", text) })?;
+        let words = value.split_whitespace().collect::<Vec<&str>>();
+        match field.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["Starting", "items"] => {
+                let items = value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(|item| number(item, line)).collect::<Result<Vec<u64>, ParseError>>()?;
+                set(&mut self.items, items, line, "Starting items")
+            },
+            ["Operation"] => {
+                let operation = Operation::parse(value).or_else(|message| error(line, message))?;
+                set(&mut self.operation, operation, line, "Operation")
+            },
+            ["Test"] => set(&mut self.divisibility, number(last_word(&words, &["divisible", "by"], line)?, line)?, line, "Test"),
+            ["If", "true"] => set(&mut self.monkey_true, number(last_word(&words, &["throw", "to", "monkey"], line)?, line)?, line, "If true"),
+            ["If", "false"] => set(&mut self.monkey_false, number(last_word(&words, &["throw", "to", "monkey"], line)?, line)?, line, "If false"),
+            _ => error(line, format!("unknown field `{}`", field.trim())),
+        }
+    }
+
+    fn build(self) -> Result<(Monkey, [(usize, usize); 2]), ParseError> {
+        let missing = |name: &str| ParseError { line: self.header, message: format!("missing {}", name) };
+        let (_, items) = self.items.ok_or_else(|| missing("Starting items"))?;
+        let (_, operation) = self.operation.ok_or_else(|| missing("Operation"))?;
+        let (line, divisibility) = self.divisibility.ok_or_else(|| missing("Test"))?;
+        let monkey_true = self.monkey_true.ok_or_else(|| missing("If true"))?;
+        let monkey_false = self.monkey_false.ok_or_else(|| missing("If false"))?;
+        if divisibility == 0 {
+            return error(line, "cannot test divisibility by 0");
+        }
+
+        let mut builder = MonkeyBuilder::new();
+        for item in items {
+            builder.add_item(item);
+        }
+        builder.set_operation(operation).set_divisibility(divisibility).set_monkey_true(monkey_true.1).set_monkey_false(monkey_false.1);
+        Ok((builder.build(), [monkey_true, monkey_false]))
+    }
+}
+
+// Monkeys in the puzzle format, at least one. They must be numbered from 0 in order, every field
+// is required once, blank lines and indentation do not matter.
+pub fn parse_monkeys(input: &str) -> Result<Vec<Monkey>, ParseError> {
+    let mut blocks: Vec<Notes> = vec![];
+    for (index, text) in input.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(id) = text.strip_prefix("Monkey") {
+            let id = id.trim().strip_suffix(':').ok_or_else(|| ParseError { line, message: "expected `Monkey <number>:`".to_string() })?;
+            if number::<usize>(id.trim(), line)? != blocks.len() {
+                return error(line, format!("expected monkey {}, found monkey {}", blocks.len(), id.trim()));
+            }
+            blocks.push(Notes { header: line, ..Notes::default() });
+            continue;
+        }
+        match blocks.last_mut() {
+            Some(notes) => notes.read(text, line)?,
+            None => return error(line, "expected `Monkey 0:` first"),
+        }
+    }
+
+    if blocks.is_empty() {
+        return error(1, "no monkey found");
+    }
+    let count = blocks.len();
+    let mut monkeys = vec![];
+    for (id, notes) in blocks.into_iter().enumerate() {
+        let (monkey, targets) = notes.build()?;
+        for (line, target) in targets {
+            if target >= count {
+                return error(line, format!("monkey {} does not exist", target));
+            }
+            if target == id {
+                return error(line, format!("monkey {} cannot throw to itself", id));
+            }
+        }
+        monkeys.push(monkey);
+    }
+    Ok(monkeys)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::{parse_monkeys, ParseError},
+        tests::EXAMPLE,
+        Operation,
+    };
+
+    fn parse_error(input: &str) -> ParseError {
+        parse_monkeys(input).unwrap_err()
+    }
+
+    #[test]
+    fn parse_example() {
+        let monkeys = parse_monkeys(EXAMPLE).unwrap();
+
+        assert_eq!(monkeys.len(), 4);
+        assert_eq!(monkeys[0].items, vec![79, 98]);
+        assert_eq!(monkeys[2].operation, Operation::from("new = old * old".to_string()));
+        assert_eq!((monkeys[3].divisibility, monkeys[3].monkey_true, monkeys[3].monkey_false), (17, 0, 1));
+    }
+
+    #[test]
+    fn parse_with_flexible_whitespace() {
+        let input = "\n\nMonkey 0 :\nStarting items:79,98\n\tOperation:   new =  old * 19\n  Test: divisible   by 23\nIf true:throw to monkey 1\n If false: throw to monkey 1\n\n\n\nMonkey 1:\n Starting items:\n Operation: new = old * old\n Test: divisible by 2\n If false: throw to monkey 0\n If true: throw to monkey 0\n";
+        let monkeys = parse_monkeys(input).unwrap();
+
+        assert_eq!(monkeys.len(), 2);
+        assert_eq!(monkeys[0].items, vec![79, 98]);
+        assert_eq!(monkeys[0].operation.apply(2), Ok(38));
+        assert!(monkeys[1].items.is_empty());
+    }
+
+    #[test]
+    fn report_errors_with_line_numbers() {
+        let example = EXAMPLE.to_string();
+
+        assert_eq!(parse_error(&example.replace("Monkey 2:", "Monkey 3:")).to_string(), "line 15: expected monkey 2, found monkey 3");
+        assert_eq!(parse_error(&example.replacen("throw to monkey 2", "throw to monkey 7", 1)).to_string(), "line 5: monkey 7 does not exist");
+        assert_eq!(parse_error(&example.replacen("throw to monkey 2", "throw to monkey 0", 1)).to_string(), "line 5: monkey 0 cannot throw to itself");
+        assert_eq!(parse_error(&example.replacen("divisible by 23", "divisible by x", 1)).to_string(), "line 4: `x` is not a number");
+        assert_eq!(parse_error(&example.replacen("divisible by 23", "multiple of 23", 1)).to_string(), "line 4: expected `divisible by <number>`");
+        assert_eq!(parse_error(&example.replacen("  Test: divisible by 23\n", "", 1)).to_string(), "line 1: missing Test");
+        assert_eq!(parse_error(&example.replacen("  Test:", "  If true: throw to monkey 1\n  Test:", 1)).to_string(), "line 6: If true already given on line 4");
+        assert_eq!(parse_error(&example.replacen("Starting", "Initial", 1)).to_string(), "line 2: unknown field `Initial items`");
+        assert_eq!(parse_error(&example.replacen("Monkey 0:\n", "", 1)).line, 1);
+        assert_eq!(parse_error("").to_string(), "line 1: no monkey found");
+        assert_eq!(parse_error("\n  \n").to_string(), "line 1: no monkey found");
+        assert_eq!(parse_error(&example.replacen("  Operation: new = old * 19\n", "  Operation: new = old ^ 19\n", 1)).line, 3);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    operation::{Operation, Operator},
+    Monkey, Relief,
+};
+
+// What happens to an item during a monkey's turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Inspect { worry: u64 },
+    // With `Relief::Modulo` the new worry level is only known modulo the product of the divisors.
+    Operate { worry: u64, relief: Relief },
+    Bored { worry: u64 },
+    Test { divisibility: u64, divisible: bool },
+    Throw { worry: u64, monkey: usize },
+}
+
+fn describe_operation(operation: &Operation, worry: u64) -> String {
+    match operation {
+        Operation::Binary(Operator::Multiply, left, right) if **left == Operation::Old && **right == Operation::Old => format!("Worry level is multiplied by itself to {}", worry),
+        Operation::Binary(Operator::Multiply, left, right) if **left == Operation::Old => format!("Worry level is multiplied by {} to {}", right, worry),
+        Operation::Binary(Operator::Add, left, right) if **left == Operation::Old => format!("Worry level increases by {} to {}", right, worry),
+        Operation::Binary(Operator::Subtract, left, right) if **left == Operation::Old => format!("Worry level decreases by {} to {}", right, worry),
+        _ => format!("Worry level becomes {} = {}", operation, worry),
+    }
+}
+
+fn narrate(operation: &Operation, event: &Event) -> String {
+    match event {
+        Event::Inspect { worry } => format!("  Monkey inspects an item with a worry level of {}.", worry),
+        Event::Operate { worry, relief: Relief::Bored } => format!("    {}.", describe_operation(operation, *worry)),
+        Event::Operate { worry, relief: Relief::Modulo(modulus) } => format!("    {} modulo {}.", describe_operation(operation, *worry), modulus),
+        Event::Bored { worry } => format!("    Monkey gets bored with item. Worry level is divided by 3 to {}.", worry),
+        Event::Test { divisibility, divisible: true } => format!("    Current worry level is divisible by {}.", divisibility),
+        Event::Test { divisibility, divisible: false } => format!("    Current worry level is not divisible by {}.", divisibility),
+        Event::Throw { worry, monkey } => format!("    Item with worry level {} is thrown to monkey {}.", worry, monkey),
+    }
+}
+
+// The turn of monkey `id` in the words of the puzzle.
+pub fn narrate_turn(id: usize, monkey: &Monkey, events: &[Event]) -> Vec<String> {
+    let mut lines = vec![format!("Monkey {}:", id)];
+    lines.extend(events.iter().map(|event| narrate(&monkey.operation, event)));
+    lines
+}
+
+pub fn holdings(monkeys: &[Monkey]) -> Vec<String> {
+    monkeys
+        .iter()
+        .enumerate()
+        .map(|(id, monkey)| format!("Monkey {}: {}", id, monkey.items.iter().map(u64::to_string).collect::<Vec<String>>().join(", ")))
+        .collect()
+}
+
+pub fn inspection_counts(monkeys: &[Monkey]) -> Vec<String> {
+    monkeys.iter().enumerate().map(|(id, monkey)| format!("Monkey {} inspected items {} times.", id, monkey.processed)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::parse_monkeys,
+        play_round,
+        tests::EXAMPLE,
+        trace::{holdings, inspection_counts, narrate_turn, Event},
+        Relief,
+    };
+
+    #[test]
+    fn events_of_first_turn() {
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let mut events = vec![];
+        let thrown = monkeys[0].process_items_with_decreasing_worryness(Some(&mut events)).unwrap();
+
+        assert_eq!(thrown, vec![(500, 3), (620, 3)]);
+        assert_eq!(
+            events[..5],
+            [
+                Event::Inspect { worry: 79 },
+                Event::Operate { worry: 1501, relief: Relief::Bored },
+                Event::Bored { worry: 500 },
+                Event::Test { divisibility: 23, divisible: false },
+                Event::Throw { worry: 500, monkey: 3 },
+            ]
+        );
+        assert_eq!(events.len(), 10);
+    }
+
+    #[test]
+    fn narrate_first_round() {
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let mut turns = vec![];
+        play_round(&mut monkeys, Relief::Bored, Some(&mut turns)).unwrap();
+
+        assert_eq!(
+            narrate_turn(0, &monkeys[0], &turns[0].1)[..6],
+            [
+                "Monkey 0:",
+                "  Monkey inspects an item with a worry level of 79.",
+                "    Worry level is multiplied by 19 to 1501.",
+                "    Monkey gets bored with item. Worry level is divided by 3 to 500.",
+                "    Current worry level is not divisible by 23.",
+                "    Item with worry level 500 is thrown to monkey 3.",
+            ]
+        );
+        assert_eq!(narrate_turn(1, &monkeys[1], &turns[1].1)[2], "    Worry level increases by 6 to 60.");
+        assert_eq!(narrate_turn(2, &monkeys[2], &turns[2].1)[2], "    Worry level is multiplied by itself to 6241.");
+        assert_eq!(narrate_turn(2, &monkeys[2], &turns[2].1)[4], "    Current worry level is divisible by 13.");
+        assert_eq!(holdings(&monkeys), ["Monkey 0: 20, 23, 27, 26", "Monkey 1: 2080, 25, 167, 207, 401, 1046", "Monkey 2: ", "Monkey 3: "]);
+    }
+
+    #[test]
+    fn count_inspections_modulo() {
+        let mut monkeys = parse_monkeys(EXAMPLE).unwrap();
+        let mut turns = vec![];
+        for _ in 0..20 {
+            turns.clear();
+            play_round(&mut monkeys, Relief::Modulo(96577), Some(&mut turns)).unwrap();
+        }
+
+        assert_eq!(inspection_counts(&monkeys), ["Monkey 0 inspected items 99 times.", "Monkey 1 inspected items 97 times.", "Monkey 2 inspected items 8 times.", "Monkey 3 inspected items 103 times."]);
+        assert!(turns.iter().flat_map(|(_, events)| events).all(|event| !matches!(event, Event::Bored { .. })));
+    }
+}
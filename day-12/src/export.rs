@@ -0,0 +1,158 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::{Heightmap, Route};
+
+// Colours at altitudes 1, 9, 18 and 26, from the valleys to the summit.
+const GRADIENT: [(u8, [u8; 3]); 4] = [(1, [30, 60, 160]), (9, [60, 150, 70]), (18, [140, 100, 50]), (26, [245, 245, 245])];
+const ROUTE: [u8; 3] = [220, 40, 40];
+const START: [u8; 3] = [250, 210, 30];
+const END: [u8; 3] = [200, 40, 200];
+
+fn altitude_to_rgb(altitude: u8) -> [u8; 3] {
+    let upper = GRADIENT.iter().position(|(stop, _)| *stop >= altitude).unwrap_or(GRADIENT.len() - 1).max(1);
+    let (low, low_color) = GRADIENT[upper - 1];
+    let (high, high_color) = GRADIENT[upper];
+    let ratio = (altitude.clamp(low, high) - low) as f64 / (high - low) as f64;
+    let mut rgb = [0; 3];
+    for channel in 0..3 {
+        rgb[channel] = (low_color[channel] as f64 + (high_color[channel] as f64 - low_color[channel] as f64) * ratio).round() as u8;
+    }
+    rgb
+}
+
+// Altitudes as colours, with the route, the start and the end drawn over them.
+fn pixels(heightmap: &Heightmap, route: Option<&Route>) -> Vec<Vec<[u8; 3]>> {
+    let mut pixels = heightmap
+        .grid
+        .iter()
+        .map(|line| line.iter().map(|cell| altitude_to_rgb(cell.altitude)).collect::<Vec<[u8; 3]>>())
+        .collect::<Vec<Vec<[u8; 3]>>>();
+    for (line, column) in route.map_or(&[][..], |route| &route.path) {
+        pixels[*line][*column] = ROUTE;
+    }
+    pixels[heightmap.start.0][heightmap.start.1] = START;
+    pixels[heightmap.end.0][heightmap.end.1] = END;
+    pixels
+}
+
+pub fn write_ppm(heightmap: &Heightmap, route: Option<&Route>, scale: usize, file_name: &str) -> io::Result<()> {
+    let pixels = pixels(heightmap, route);
+    let mut writer = BufWriter::new(File::create(file_name)?);
+    write!(writer, "P6\n{} {}\n255\n", pixels[0].len() * scale, pixels.len() * scale)?;
+    for line in &pixels {
+        for _ in 0..scale {
+            for pixel in line {
+                for _ in 0..scale {
+                    writer.write_all(pixel)?;
+                }
+            }
+        }
+    }
+    writer.flush()
+}
+
+struct Mesh {
+    vertices: Vec<(usize, usize, usize)>,
+    faces: Vec<[usize; 4]>,
+}
+
+impl Mesh {
+    // Corners are given counterclockwise when seen from outside.
+    fn quad(&mut self, corners: [(usize, usize, usize); 4]) {
+        let first = self.vertices.len() + 1;
+        self.vertices.extend(corners);
+        self.faces.push([first, first + 1, first + 2, first + 3]);
+    }
+}
+
+// Wavefront OBJ with `y` going up: every cell is a column as high as its altitude, walls are only
+// added where the neighbouring column is lower.
+pub fn to_obj(heightmap: &Heightmap) -> String {
+    let lines = heightmap.grid.len();
+    let columns = heightmap.grid[0].len();
+    let height = |line: isize, column: isize| {
+        if (0..lines as isize).contains(&line) && (0..columns as isize).contains(&column) {
+            heightmap.grid[line as usize][column as usize].altitude as usize
+        } else {
+            0
+        }
+    };
+    let mut mesh = Mesh { vertices: vec![], faces: vec![] };
+    for (line, column) in heightmap.cells() {
+        let (l, c) = (line as isize, column as isize);
+        let top = height(l, c);
+        let (x, z) = (column, line);
+        mesh.quad([(x, top, z), (x, top, z + 1), (x + 1, top, z + 1), (x + 1, top, z)]);
+        let north = height(l - 1, c);
+        if north < top {
+            mesh.quad([(x, north, z), (x, top, z), (x + 1, top, z), (x + 1, north, z)]);
+        }
+        let south = height(l + 1, c);
+        if south < top {
+            mesh.quad([(x + 1, south, z + 1), (x + 1, top, z + 1), (x, top, z + 1), (x, south, z + 1)]);
+        }
+        let west = height(l, c - 1);
+        if west < top {
+            mesh.quad([(x, west, z + 1), (x, top, z + 1), (x, top, z), (x, west, z)]);
+        }
+        let east = height(l, c + 1);
+        if east < top {
+            mesh.quad([(x + 1, east, z), (x + 1, top, z), (x + 1, top, z + 1), (x + 1, east, z + 1)]);
+        }
+    }
+    mesh.quad([(0, 0, 0), (columns, 0, 0), (columns, 0, lines), (0, 0, lines)]);
+
+    let mut obj = String::from("o terrain\n");
+    for (x, y, z) in &mesh.vertices {
+        obj += &format!("v {} {} {}\n", x, y, z);
+    }
+    for face in &mesh.faces {
+        obj += &format!("f {} {} {} {}\n", face[0], face[1], face[2], face[3]);
+    }
+    obj
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        export::{altitude_to_rgb, pixels, to_obj, END, GRADIENT, ROUTE, START},
+        tests::EXAMPLE,
+        Heightmap,
+    };
+
+    #[test]
+    fn colors_follow_the_gradient() {
+        assert_eq!(altitude_to_rgb(1), GRADIENT[0].1);
+        assert_eq!(altitude_to_rgb(9), GRADIENT[1].1);
+        assert_eq!(altitude_to_rgb(26), GRADIENT[3].1);
+        assert_eq!(altitude_to_rgb(5), [45, 105, 115]);
+    }
+
+    #[test]
+    fn route_is_drawn_over_the_terrain() {
+        let mut heightmap = Heightmap::from(EXAMPLE);
+        let (route, _) = heightmap.climb().unwrap();
+        let pixels = pixels(&heightmap, Some(&route));
+
+        assert_eq!(pixels[0][0], START);
+        assert_eq!(pixels[2][5], END);
+        assert_eq!(pixels.iter().flatten().filter(|pixel| **pixel == ROUTE).count(), 30);
+        assert_eq!(pixels[4][0], altitude_to_rgb(1));
+    }
+
+    #[test]
+    fn mesh_of_two_columns() {
+        // Columns of heights 1 and 3: two tops, three walls for the first as its east side is
+        // higher, four for the second, and the bottom.
+        let obj = to_obj(&Heightmap::from("Sc"));
+
+        assert_eq!(obj.lines().filter(|line| line.starts_with("f ")).count(), 10);
+        assert_eq!(obj.lines().filter(|line| line.starts_with("v ")).count(), 40);
+        assert!(obj.contains("v 1 3 0\nv 1 3 1\nv 2 3 1\nv 2 3 0\n"));
+        assert!(obj.contains("v 0 0 0\nv 2 0 0\nv 2 0 1\nv 0 0 1\nf 1 2 3 4\n"));
+        assert!(obj.ends_with("f 37 38 39 40\n"));
+    }
+}
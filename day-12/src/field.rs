@@ -0,0 +1,88 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use crate::Route;
+
+// Result of a complete search from the end: distance and next step towards the end of every cell,
+// `None` where the end cannot be reached.
+#[derive(Debug)]
+pub struct DistanceField {
+    pub distances: Vec<Vec<Option<usize>>>,
+    pub next: Vec<Vec<Option<(usize, usize)>>>,
+}
+
+impl DistanceField {
+    pub fn distance(&self, (line, column): (usize, usize)) -> Option<usize> {
+        self.distances[line][column]
+    }
+
+    pub fn route_from(&self, cell: (usize, usize)) -> Option<Route> {
+        self.distance(cell)?;
+        let mut path = vec![cell];
+        let mut current = cell;
+        while let Some(next) = self.next[current.0][current.1] {
+            path.push(next);
+            current = next;
+        }
+        Some(Route { path })
+    }
+
+    pub fn reachable(&self) -> usize {
+        self.distances.iter().flatten().filter(|distance| distance.is_some()).count()
+    }
+
+    fn max_distance(&self) -> usize {
+        self.distances.iter().flatten().flatten().copied().max().unwrap_or(0)
+    }
+
+    // One line per row of the map, unreachable cells are left empty.
+    pub fn to_csv(&self) -> String {
+        self.distances
+            .iter()
+            .map(|line| line.iter().map(|distance| distance.map_or(String::new(), |distance| distance.to_string())).collect::<Vec<String>>().join(","))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    // The end is white and cells get darker the further they are, unreachable cells are black.
+    pub fn write_pgm(&self, file_name: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(file_name)?);
+        write!(writer, "P5\n{} {}\n255\n", self.distances[0].len(), self.distances.len())?;
+        let max_distance = self.max_distance().max(1);
+        for distance in self.distances.iter().flatten() {
+            let gray = match distance {
+                None => 0,
+                Some(distance) => (255 - distance * 223 / max_distance) as u8,
+            };
+            writer.write_all(&[gray])?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::EXAMPLE, Heightmap};
+
+    #[test]
+    fn distances_of_example() {
+        let mut heightmap = Heightmap::from(EXAMPLE);
+        let field = heightmap.distance_field();
+
+        assert_eq!(field.distance(heightmap.start), Some(31));
+        assert_eq!(field.distance(heightmap.end), Some(0));
+        assert_eq!(field.route_from((4, 0)).unwrap().steps(), 29);
+        assert_eq!(field.reachable(), 40);
+    }
+
+    #[test]
+    fn unreachable_cells_are_marked() {
+        let mut heightmap = Heightmap::from("SbcdE\naaaaz");
+        let field = heightmap.distance_field();
+
+        assert_eq!(field.to_csv(), ",,,,0\n,,,,1\n");
+        assert_eq!(field.route_from(heightmap.start), None);
+    }
+}
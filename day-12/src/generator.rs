@@ -0,0 +1,218 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::Rules;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratorOptions {
+    pub lines: usize,
+    pub columns: usize,
+    // Largest altitude change between a cell and the average of the cells above and on its left.
+    pub roughness: u8,
+    pub solvable: bool,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions { lines: 41, columns: 161, roughness: 2, solvable: true }
+    }
+}
+
+fn manhattan(a: (usize, usize), b: (usize, usize)) -> usize {
+    a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
+}
+
+pub struct HeightmapGenerator {
+    rng: StdRng,
+    options: GeneratorOptions,
+}
+
+impl HeightmapGenerator {
+    pub fn new(seed: u64, options: GeneratorOptions) -> Self {
+        HeightmapGenerator { rng: StdRng::seed_from_u64(seed), options }
+    }
+
+    fn cell(&mut self) -> (usize, usize) {
+        (self.rng.gen_range(0..self.options.lines), self.rng.gen_range(0..self.options.columns))
+    }
+
+    fn furthest_corner(&self, (line, column): (usize, usize)) -> (usize, usize) {
+        let line = if line * 2 < self.options.lines { self.options.lines - 1 } else { 0 };
+        let column = if column * 2 < self.options.columns { self.options.columns - 1 } else { 0 };
+        (line, column)
+    }
+
+    fn terrain(&mut self) -> Vec<Vec<u8>> {
+        let roughness = self.options.roughness as i64;
+        let mut altitudes = vec![vec![0u8; self.options.columns]; self.options.lines];
+        for line in 0..self.options.lines {
+            for column in 0..self.options.columns {
+                let around = match (line, column) {
+                    (0, 0) => self.rng.gen_range(1..=26),
+                    (0, _) => altitudes[line][column - 1] as i64,
+                    (_, 0) => altitudes[line - 1][column] as i64,
+                    _ => (altitudes[line - 1][column] as i64 + altitudes[line][column - 1] as i64 + 1) / 2,
+                };
+                let change = self.rng.gen_range(-roughness..=roughness);
+                altitudes[line][column] = (around + change).clamp(1, 26) as u8;
+            }
+        }
+        altitudes
+    }
+
+    // A staircase from `start` to `end` going up evenly: each of its steps climbs at most
+    // `max_ascent` as long as it is at least 25 / `max_ascent` steps long, and never goes down.
+    fn carve(&mut self, altitudes: &mut [Vec<u8>], start: (usize, usize), end: (usize, usize)) {
+        let length = manhattan(start, end);
+        let mut cell = start;
+        for step in 0..length {
+            altitudes[cell.0][cell.1] = 1 + (25 * step).div_ceil(length) as u8;
+            let vertical = cell.0 != end.0 && (cell.1 == end.1 || self.rng.gen_bool(0.5));
+            cell = match vertical {
+                true if cell.0 < end.0 => (cell.0 + 1, cell.1),
+                true => (cell.0 - 1, cell.1),
+                false if cell.1 < end.1 => (cell.0, cell.1 + 1),
+                false => (cell.0, cell.1 - 1),
+            };
+        }
+    }
+
+    // Puts every cell around the end too low to step onto it.
+    fn moat(&mut self, altitudes: &mut [Vec<u8>], end: (usize, usize), max_ascent: u8) {
+        for line in altitudes.iter_mut().skip(end.0.saturating_sub(1)).take(if end.0 == 0 { 2 } else { 3 }) {
+            for altitude in line.iter_mut().skip(end.1.saturating_sub(1)).take(if end.1 == 0 { 2 } else { 3 }) {
+                *altitude = (*altitude).min(25 - max_ascent);
+            }
+        }
+    }
+
+    // A map with a route from `S` to `E` under `rules` when `options.solvable`, without one
+    // otherwise. `None` when the rules make that impossible: a grid too small to climb up to the
+    // end, or an end that can be reached from anywhere.
+    pub fn heightmap(&mut self, rules: &Rules) -> Option<String> {
+        let steps_needed = 25usize.div_ceil(rules.max_ascent.max(1) as usize);
+        if self.options.lines * self.options.columns < 2
+            || (self.options.solvable && (rules.max_ascent == 0 || self.options.lines + self.options.columns - 2 < steps_needed))
+            || (!self.options.solvable && rules.max_ascent >= 25)
+        {
+            return None;
+        }
+
+        let mut altitudes = self.terrain();
+        let solvable = self.options.solvable;
+        let far_enough = |distance: usize| !solvable || distance >= steps_needed;
+        // Random cells far enough from each other, or else the furthest corner for the start and a
+        // corner for the end.
+        let mut end = self.cell();
+        for _ in 0..100 {
+            if far_enough(manhattan(end, self.furthest_corner(end))) {
+                break;
+            }
+            end = self.cell();
+        }
+        if !far_enough(manhattan(end, self.furthest_corner(end))) {
+            end = (0, 0);
+        }
+        let mut start = self.cell();
+        for _ in 0..100 {
+            if start != end && far_enough(manhattan(start, end)) {
+                break;
+            }
+            start = self.cell();
+        }
+        if start == end || !far_enough(manhattan(start, end)) {
+            start = self.furthest_corner(end);
+        }
+
+        if self.options.solvable {
+            self.carve(&mut altitudes, start, end);
+        } else {
+            self.moat(&mut altitudes, end, rules.max_ascent);
+        }
+        let map = altitudes
+            .iter()
+            .enumerate()
+            .map(|(line, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(column, altitude)| match (line, column) {
+                        cell if cell == start => 'S',
+                        cell if cell == end => 'E',
+                        _ => (b'a' + altitude - 1) as char,
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>();
+        Some(map.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        generator::{GeneratorOptions, HeightmapGenerator},
+        pathfinding::STRATEGIES,
+        tests::assert_valid,
+        Heightmap, Rules,
+    };
+
+    const RULES: [&str; 5] = ["", "neighbors=8", "ascent=3 descent=2", "ascent=2 cost=1,2,1", "descent=0"];
+
+    fn options(lines: usize, columns: usize, roughness: u8, solvable: bool) -> GeneratorOptions {
+        GeneratorOptions { lines, columns, roughness, solvable }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let first = HeightmapGenerator::new(7, GeneratorOptions::default()).heightmap(&Rules::default());
+        let second = HeightmapGenerator::new(7, GeneratorOptions::default()).heightmap(&Rules::default());
+
+        assert_eq!(first, second);
+        let map = first.unwrap();
+        assert_eq!(map.lines().count(), 41);
+        assert!(map.lines().all(|line| line.len() == 161));
+        assert_eq!(map.matches('S').count(), 1);
+        assert_eq!(map.matches('E').count(), 1);
+    }
+
+    #[test]
+    fn solvable_maps_have_a_route() {
+        for (seed, rules) in (0..40).zip(RULES.iter().cycle()) {
+            let rules = Rules::parse(rules).unwrap();
+            let sizes = [(5, 30), (30, 5), (20, 20), (14, 14)];
+            let (lines, columns) = sizes[seed as usize % sizes.len()];
+            let map = HeightmapGenerator::new(seed, options(lines, columns, seed as u8 % 5, true)).heightmap(&rules).unwrap();
+            let mut heightmap = Heightmap::from(map.as_str()).with_rules(rules);
+            let (route, _) = heightmap.climb().unwrap_or_else(|| panic!("No route with seed {}:\n{}", seed, map));
+
+            assert_valid(&heightmap, &route);
+            let cost = heightmap.cost(&route);
+            for strategy in STRATEGIES {
+                let route = heightmap.find_route(heightmap.start, strategy).unwrap();
+                assert_eq!(heightmap.cost(&route), cost, "{:?} with seed {}", strategy, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn unsolvable_maps_have_no_route() {
+        for (seed, rules) in (0..40).zip(RULES.iter().cycle()) {
+            let rules = Rules::parse(rules).unwrap();
+            let map = HeightmapGenerator::new(seed, options(15, 25, 3, false)).heightmap(&rules).unwrap();
+            let mut heightmap = Heightmap::from(map.as_str()).with_rules(rules);
+
+            assert!(heightmap.climb().is_none(), "Route with seed {}:\n{}", seed, map);
+            for strategy in STRATEGIES {
+                assert_eq!(heightmap.find_route(heightmap.start, strategy), None);
+            }
+            assert_eq!(heightmap.distance_field().distance(heightmap.start), None);
+        }
+    }
+
+    #[test]
+    fn impossible_requests() {
+        assert_eq!(HeightmapGenerator::new(1, options(3, 3, 1, true)).heightmap(&Rules::default()), None);
+        assert!(HeightmapGenerator::new(1, options(3, 3, 1, true)).heightmap(&Rules::parse("ascent=25").unwrap()).is_some());
+        assert_eq!(HeightmapGenerator::new(1, options(3, 3, 1, false)).heightmap(&Rules::parse("ascent=25").unwrap()), None);
+        assert_eq!(HeightmapGenerator::new(1, options(1, 1, 1, false)).heightmap(&Rules::default()), None);
+    }
+}
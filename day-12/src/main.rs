@@ -1,8 +1,19 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    env, fs,
 };
 
+use export::{to_obj, write_ppm};
+use field::DistanceField;
+use generator::{GeneratorOptions, HeightmapGenerator};
+use pathfinding::{benchmark, Strategy};
+
+mod export;
+mod field;
+mod generator;
+mod pathfinding;
+
 #[derive(PartialEq, Debug)]
 struct Cell {
     is_start: bool,
@@ -76,88 +87,494 @@ fn get_neighbors(
     (line, column): (usize, usize),
     number_of_lines: usize,
     number_of_columns: usize,
+    diagonals: bool,
 ) -> Vec<(usize, usize)> {
     let mut neighbors = vec![];
-    if line > 0 {
-        neighbors.push((line - 1, column));
+    for (line_offset, column_offset) in [(-1, 0), (0, -1), (1, 0), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+        if !diagonals && line_offset != 0 && column_offset != 0 {
+            continue;
+        }
+        let neighbor_line = line as isize + line_offset;
+        let neighbor_column = column as isize + column_offset;
+        if (0..number_of_lines as isize).contains(&neighbor_line) && (0..number_of_columns as isize).contains(&neighbor_column) {
+            neighbors.push((neighbor_line as usize, neighbor_column as usize));
+        }
     }
-    if column > 0 {
-        neighbors.push((line, column - 1));
+    neighbors
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Rules {
+    max_ascent: u8,
+    // `None` lets us jump down from any height.
+    max_descent: Option<u8>,
+    diagonals: bool,
+    // Cost of a step is `step_cost`, plus `ascent_cost` per level climbed and `descent_cost` per
+    // level descended.
+    step_cost: usize,
+    ascent_cost: usize,
+    descent_cost: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { max_ascent: 1, max_descent: None, diagonals: false, step_cost: 1, ascent_cost: 0, descent_cost: 0 }
     }
-    if line < number_of_lines - 1 {
-        neighbors.push((line + 1, column));
+}
+
+fn setting_number<T: std::str::FromStr>(setting: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("`{}` is not a number in `{}`", value, setting))
+}
+
+impl Rules {
+    // Whitespace separated settings on top of the defaults, e.g. "ascent=2 descent=3 neighbors=8 cost=1,2,0".
+    fn parse(input: &str) -> Result<Self, String> {
+        let mut rules = Rules::default();
+        for setting in input.split_whitespace() {
+            let (key, value) = setting.split_once('=').ok_or_else(|| format!("expected `key=value`, found `{}`", setting))?;
+            match key {
+                "ascent" => rules.max_ascent = setting_number(setting, value)?,
+                "descent" => rules.max_descent = Some(setting_number(setting, value)?),
+                "neighbors" => match value {
+                    "4" => rules.diagonals = false,
+                    "8" => rules.diagonals = true,
+                    _ => return Err(format!("expected 4 or 8 neighbors, found `{}`", value)),
+                },
+                "cost" => {
+                    let costs = value.split(',').map(|cost| setting_number(setting, cost)).collect::<Result<Vec<usize>, String>>()?;
+                    let [step, ascent, descent] = costs[..] else {
+                        return Err(format!("expected `cost=<step>,<ascent>,<descent>`, found `{}`", setting));
+                    };
+                    (rules.step_cost, rules.ascent_cost, rules.descent_cost) = (step, ascent, descent);
+                },
+                _ => return Err(format!("unknown setting `{}`", key)),
+            }
+        }
+        Ok(rules)
     }
-    if column < number_of_columns - 1 {
-        neighbors.push((line, column + 1));
+}
+
+impl Rules {
+    fn allows(&self, from: u8, to: u8) -> bool {
+        to <= from.saturating_add(self.max_ascent) && self.max_descent.is_none_or(|max_descent| from <= to.saturating_add(max_descent))
+    }
+
+    fn cost(&self, from: u8, to: u8) -> usize {
+        self.step_cost + self.ascent_cost * to.saturating_sub(from) as usize + self.descent_cost * from.saturating_sub(to) as usize
+    }
+
+    // All steps cost the same, so a breadth-first search is enough.
+    fn is_uniform(&self) -> bool {
+        self.ascent_cost == 0 && self.descent_cost == 0
     }
-    neighbors
 }
 
-fn main() {
-    let file_name = "input/day-12";
-    let file = File::open(file_name).expect("oops");
-    let reader = BufReader::new(file);
-
-    let mut grid = reader
-        .lines()
-        .map(|line| {
-            line.unwrap()
-                .chars()
-                .map(|c| Cell::from(c))
-                .collect::<Vec<Cell>>()
-        })
-        .collect::<Vec<Vec<Cell>>>();
-
-    // We start from the end and go back to the start so we can use the same algorithm on both problems
-    let mut end_cell = (0, 0);
-    for (line_index, line) in grid.iter().enumerate() {
-        for (column_index, cell) in line.iter().enumerate() {
-            if cell.is_end {
-                end_cell = (line_index, column_index);
+#[derive(Debug, PartialEq)]
+struct Route {
+    path: Vec<(usize, usize)>,
+}
+
+impl Route {
+    fn steps(&self) -> usize {
+        self.path.len() - 1
+    }
+}
+
+#[derive(Debug)]
+struct Heightmap {
+    grid: Vec<Vec<Cell>>,
+    start: (usize, usize),
+    end: (usize, usize),
+    rules: Rules,
+}
+
+impl From<&str> for Heightmap {
+    fn from(input: &str) -> Self {
+        let grid = input
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().map(Cell::from).collect::<Vec<Cell>>())
+            .collect::<Vec<Vec<Cell>>>();
+        let mut start = (0, 0);
+        let mut end = (0, 0);
+        for (line_index, line) in grid.iter().enumerate() {
+            for (column_index, cell) in line.iter().enumerate() {
+                if cell.is_start {
+                    start = (line_index, column_index);
+                }
+                if cell.is_end {
+                    end = (line_index, column_index);
+                }
+            }
+        }
+        Heightmap { grid, start, end, rules: Rules::default() }
+    }
+}
+
+impl Heightmap {
+    fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    // Cells from which we are allowed to step onto `cell`.
+    fn predecessors(&self, cell: (usize, usize)) -> Vec<(usize, usize)> {
+        let altitude = self.grid[cell.0][cell.1].altitude;
+        get_neighbors(cell, self.grid.len(), self.grid[0].len(), self.rules.diagonals)
+            .into_iter()
+            .filter(|(line, column)| self.rules.allows(self.grid[*line][*column].altitude, altitude))
+            .collect()
+    }
+
+    // Cells we are allowed to step onto from `cell`.
+    fn successors(&self, cell: (usize, usize)) -> Vec<(usize, usize)> {
+        let altitude = self.grid[cell.0][cell.1].altitude;
+        get_neighbors(cell, self.grid.len(), self.grid[0].len(), self.rules.diagonals)
+            .into_iter()
+            .filter(|(line, column)| self.rules.allows(altitude, self.grid[*line][*column].altitude))
+            .collect()
+    }
+
+    fn step_cost(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        self.rules.cost(self.grid[from.0][from.1].altitude, self.grid[to.0][to.1].altitude)
+    }
+
+    fn cost(&self, route: &Route) -> usize {
+        route.path.windows(2).map(|step| self.step_cost(step[0], step[1])).sum()
+    }
+
+    // We start from the end and go back to the start so we can use the same search on both
+    // problems. Each reached cell keeps in `previous` its next step towards the end.
+    // Stops once `stop_at` is reached, otherwise explores everything reachable. Returns the
+    // cost to reach the end from every reached cell.
+    fn explore(&mut self, stop_at: Option<(usize, usize)>) -> Vec<Vec<Option<usize>>> {
+        for cell in self.grid.iter_mut().flatten() {
+            cell.visited = false;
+            cell.previous = None;
+        }
+        let mut distances = vec![vec![None; self.grid[0].len()]; self.grid.len()];
+        distances[self.end.0][self.end.1] = Some(0);
+        if self.rules.is_uniform() {
+            self.breadth_first(stop_at, &mut distances);
+        } else {
+            self.dijkstra(stop_at, &mut distances);
+        }
+        distances
+    }
+
+    fn breadth_first(&mut self, stop_at: Option<(usize, usize)>, distances: &mut [Vec<Option<usize>>]) {
+        let mut cells_to_check = vec![self.end];
+        self.grid[self.end.0][self.end.1].visited = true;
+        for step in 1.. {
+            if cells_to_check.is_empty() || stop_at.is_some_and(|cell| cells_to_check.contains(&cell)) {
                 break;
             }
+            let mut next_cells_to_check = vec![];
+            for cell in &cells_to_check {
+                let neighbors_to_visit = self
+                    .predecessors(*cell)
+                    .into_iter()
+                    .filter(|(line, column)| !self.grid[*line][*column].visited)
+                    .collect::<Vec<(usize, usize)>>();
+                for neighbor in neighbors_to_visit {
+                    let neighbor_cell = &mut self.grid[neighbor.0][neighbor.1];
+                    neighbor_cell.visited = true;
+                    neighbor_cell.previous = Some(*cell);
+                    distances[neighbor.0][neighbor.1] = Some(step * self.rules.step_cost);
+                    next_cells_to_check.push(neighbor);
+                }
+            }
+            cells_to_check = next_cells_to_check;
         }
     }
-    let lines = grid.len();
-    let columns = grid[0].len();
 
-    let mut cells_to_check = vec![end_cell];
-    let mut path_length_to_start = 0;
-    let mut path_length_to_low_cell = 0;
-    'outer: for step in 0.. {
-        let mut next_cells_to_check = vec![];
-        for cell in &cells_to_check {
-            if grid[cell.0][cell.1].is_start {
-                path_length_to_start = step;
-                break 'outer;
+    // Here `visited` marks the cells whose cost is final.
+    fn dijkstra(&mut self, stop_at: Option<(usize, usize)>, distances: &mut [Vec<Option<usize>>]) {
+        let mut queue = BinaryHeap::from([Reverse((0, self.end))]);
+        while let Some(Reverse((distance, cell))) = queue.pop() {
+            if self.grid[cell.0][cell.1].visited {
+                continue;
             }
-            if grid[cell.0][cell.1].altitude == 1 && path_length_to_low_cell == 0 {
-                path_length_to_low_cell = step;
+            self.grid[cell.0][cell.1].visited = true;
+            if stop_at == Some(cell) {
+                break;
             }
-            grid[cell.0][cell.1].visited = true;
-            let mut neighbors_to_visit = get_neighbors(*cell, lines, columns)
-                .iter()
-                .filter(|(line, column)| !grid[*line][*column].visited)
-                .filter(|(line, column)| {
-                    let current_altitude = grid[cell.0][cell.1].altitude;
-                    let neighbor_altitude = grid[*line][*column].altitude;
-                    neighbor_altitude + 1 >= current_altitude
-                })
-                .map(|cell| *cell)
-                .collect::<Vec<(usize, usize)>>();
-            for neighbor in &neighbors_to_visit {
-                grid[neighbor.0][neighbor.1].previous = Some((cell.0, cell.1));
+            for neighbor in self.predecessors(cell) {
+                let neighbor_distance = distance + self.step_cost(neighbor, cell);
+                if self.grid[neighbor.0][neighbor.1].visited || distances[neighbor.0][neighbor.1].is_some_and(|known| known <= neighbor_distance) {
+                    continue;
+                }
+                distances[neighbor.0][neighbor.1] = Some(neighbor_distance);
+                self.grid[neighbor.0][neighbor.1].previous = Some(cell);
+                queue.push(Reverse((neighbor_distance, neighbor)));
             }
-            next_cells_to_check.append(&mut neighbors_to_visit);
         }
-        if next_cells_to_check.is_empty() {
-            panic!("Should not be empty");
+    }
+
+    // Returns the routes from the start and from the closest low cell, `None` when the end
+    // cannot be reached from the start.
+    fn climb(&mut self) -> Option<(Route, Route)> {
+        let distances = self.explore(Some(self.start));
+        distances[self.start.0][self.start.1]?;
+        let low_cell = self
+            .cells()
+            .filter(|(line, column)| self.grid[*line][*column].altitude == 1)
+            .filter_map(|(line, column)| distances[line][column].map(|distance| (distance, (line, column))))
+            .min_by_key(|(distance, _)| *distance)
+            .map_or(self.start, |(_, cell)| cell);
+        Some((self.route_from(self.start), self.route_from(low_cell)))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.grid.iter().enumerate().flat_map(|(line, cells)| (0..cells.len()).map(move |column| (line, column)))
+    }
+
+    fn distance_field(&mut self) -> DistanceField {
+        let distances = self.explore(None);
+        let next = self.grid.iter().map(|line| line.iter().map(|cell| cell.previous).collect()).collect();
+        DistanceField { distances, next }
+    }
+
+    fn route_from(&self, cell: (usize, usize)) -> Route {
+        let mut path = vec![cell];
+        let mut current = cell;
+        while let Some(next) = self.grid[current.0][current.1].previous {
+            path.push(next);
+            current = next;
+        }
+        Route { path }
+    }
+
+    // Draws the route with arrows as in the puzzle text.
+    fn render(&self, route: &Route) -> String {
+        let mut lines = self.grid.iter().map(|line| vec!['.'; line.len()]).collect::<Vec<Vec<char>>>();
+        for step in route.path.windows(2) {
+            let ((line, column), (next_line, next_column)) = (step[0], step[1]);
+            lines[line][column] = match (next_line.cmp(&line), next_column.cmp(&column)) {
+                (Ordering::Less, Ordering::Equal) => '^',
+                (Ordering::Greater, Ordering::Equal) => 'v',
+                (Ordering::Equal, Ordering::Less) => '<',
+                (Ordering::Equal, _) => '>',
+                (Ordering::Less, Ordering::Greater) | (Ordering::Greater, Ordering::Less) => '/',
+                _ => '\\',
+            };
         }
-        next_cells_to_check.sort();
-        next_cells_to_check.dedup();
-        cells_to_check = next_cells_to_check;
+        let (line, column) = *route.path.last().unwrap();
+        lines[line][column] = 'E';
+        lines.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+    }
+}
+
+fn main() {
+    if env::args().nth(1).as_deref() == Some("generate") {
+        let mut options = GeneratorOptions::default();
+        let arguments = env::args().skip(2).collect::<Vec<String>>();
+        if let [lines, columns, ..] = &arguments[..] {
+            (options.lines, options.columns) = (lines.parse().unwrap(), columns.parse().unwrap());
+        }
+        let seed = arguments.get(2).map_or(0, |seed| seed.parse().unwrap());
+        options.roughness = arguments.get(3).map_or(options.roughness, |roughness| roughness.parse().unwrap());
+        options.solvable = arguments.get(4).map(String::as_str) != Some("unsolvable");
+        let rules = match arguments.get(5).map_or(Ok(Rules::default()), |rules| Rules::parse(&rules.replace(';', " "))) {
+            Ok(rules) => rules,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            },
+        };
+        match HeightmapGenerator::new(seed, options).heightmap(&rules) {
+            Some(map) => println!("{}", map),
+            None => println!("No such map with these rules"),
+        }
+        return;
+    }
+
+    if env::args().nth(1).as_deref() == Some("bench") {
+        let size = env::args().nth(2).map_or(500, |size| size.parse().unwrap());
+        let rules = match env::args().nth(3).map_or(Ok(Rules::default()), |rules| Rules::parse(&rules.replace(';', " "))) {
+            Ok(rules) => rules,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            },
+        };
+        let options = GeneratorOptions { lines: size, columns: size, ..GeneratorOptions::default() };
+        let Some(map) = HeightmapGenerator::new(0, options).heightmap(&rules) else {
+            println!("No such map with these rules");
+            return;
+        };
+        let mut heightmap = Heightmap::from(map.as_str()).with_rules(rules);
+        let start = heightmap.start;
+        let results = benchmark(&mut heightmap, start);
+        for (strategy, elapsed, cost) in &results {
+            println!("{:?}: {:?} in {:?}", strategy, cost, elapsed);
+        }
+        assert!(results.iter().all(|(_, _, cost)| *cost == results[0].2), "Strategies disagree");
+        return;
+    }
+
+    let file_name = "input/day-12";
+    let content = fs::read_to_string(file_name).expect("oops");
+    let mut heightmap = Heightmap::from(content.as_str());
+    if let Some(rules) = env::args().nth(1).as_deref().and_then(|mode| mode.strip_prefix("rules=")) {
+        match Rules::parse(&rules.replace(';', " ")) {
+            Ok(rules) => heightmap = heightmap.with_rules(rules),
+            Err(error) => {
+                println!("{}", error);
+                return;
+            },
+        }
+    }
+
+    match env::args().nth(1).as_deref() {
+        Some("image") => {
+            let image_file = env::args().nth(2).unwrap_or("day-12.ppm".to_string());
+            let scale = env::args().nth(3).map_or(4, |scale| scale.parse().unwrap());
+            let route = heightmap.find_route(heightmap.start, Strategy::Explore);
+            write_ppm(&heightmap, route.as_ref(), scale, &image_file).expect("Could not write the image");
+            println!("Heightmap written to {}", image_file);
+            return;
+        },
+        Some("mesh") => {
+            let mesh_file = env::args().nth(2).unwrap_or("day-12.obj".to_string());
+            fs::write(&mesh_file, to_obj(&heightmap)).expect("Could not write the mesh");
+            println!("Mesh written to {}", mesh_file);
+            return;
+        },
+        _ => {},
+    }
+
+    if env::args().nth(1).as_deref() == Some("field") {
+        let field = heightmap.distance_field();
+        let csv_file = env::args().nth(2).unwrap_or("day-12-distances.csv".to_string());
+        let image_file = env::args().nth(3).unwrap_or("day-12-distances.pgm".to_string());
+        fs::write(&csv_file, field.to_csv()).expect("Could not write the distances");
+        field.write_pgm(&image_file).expect("Could not write the image");
+        println!("Distances written to {} and {}", csv_file, image_file);
+        println!("{} cells can reach the end", field.reachable());
+        if let Some(route) = field.route_from(heightmap.start) {
+            println!("{}", heightmap.render(&route));
+            println!("{} steps to reach start", route.steps());
+        }
+        return;
+    }
+
+    let Some((route_from_start, route_from_low_cell)) = heightmap.climb() else {
+        println!("The end cannot be reached from the start");
+        return;
     };
+    println!("{}", heightmap.render(&route_from_start));
+    println!("{} steps to reach start", route_from_start.steps());
+    println!("{}", heightmap.render(&route_from_low_cell));
+    println!("{} steps to reach low cell", route_from_low_cell.steps());
+    if !heightmap.rules.is_uniform() {
+        println!("Costs {} from start and {} from low cell", heightmap.cost(&route_from_start), heightmap.cost(&route_from_low_cell));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{get_neighbors, Heightmap, Route, Rules};
+
+    pub const EXAMPLE: &str = "Sabqponm\nabcryxxl\naccszExk\nacctuvwj\nabdefghi";
+
+    pub fn assert_valid(heightmap: &Heightmap, route: &Route) {
+        for step in route.path.windows(2) {
+            assert!(get_neighbors(step[0], heightmap.grid.len(), heightmap.grid[0].len(), heightmap.rules.diagonals).contains(&step[1]));
+            let altitude = heightmap.grid[step[0].0][step[0].1].altitude;
+            assert!(heightmap.rules.allows(altitude, heightmap.grid[step[1].0][step[1].1].altitude));
+        }
+        assert_eq!(*route.path.last().unwrap(), heightmap.end);
+    }
+
+    #[test]
+    fn climb_example() {
+        let mut heightmap = Heightmap::from(EXAMPLE);
+        let (route_from_start, route_from_low_cell) = heightmap.climb().unwrap();
+
+        assert_eq!(route_from_start.steps(), 31);
+        assert_eq!(route_from_start.path[0], (0, 0));
+        assert_valid(&heightmap, &route_from_start);
+        assert_eq!(route_from_low_cell.steps(), 29);
+        assert_eq!(heightmap.grid[route_from_low_cell.path[0].0][route_from_low_cell.path[0].1].altitude, 1);
+        assert_valid(&heightmap, &route_from_low_cell);
+    }
+
+    #[test]
+    fn render_route_with_arrows() {
+        let heightmap = Heightmap::from(EXAMPLE);
+        let route = Route { path: vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 2)] };
+
+        assert_eq!(heightmap.render(&route), "v>E.....\n>^......\n........\n........\n........");
+    }
+
+    #[test]
+    fn render_example_route() {
+        let mut heightmap = Heightmap::from(EXAMPLE);
+        let (route, _) = heightmap.climb().unwrap();
+        let rendered = heightmap.render(&route);
+
+        assert_eq!(rendered.chars().filter(|c| "<>^v".contains(*c)).count(), 31);
+        assert_eq!(rendered.lines().nth(2).unwrap().chars().nth(5), Some('E'));
+    }
 
-    println!("{} steps to reach start", path_length_to_start);
-    println!("{} steps to reach low cell", path_length_to_low_cell);
+    #[test]
+    fn parse_rules() {
+        let rules = Rules::parse("ascent=2 descent=3 neighbors=8 cost=1,2,0").unwrap();
+
+        assert_eq!(rules, Rules { max_ascent: 2, max_descent: Some(3), diagonals: true, step_cost: 1, ascent_cost: 2, descent_cost: 0 });
+        assert_eq!(Rules::parse("").unwrap(), Rules::default());
+        assert!(rules.allows(5, 7) && !rules.allows(5, 8));
+        assert!(rules.allows(5, 2) && !rules.allows(5, 1));
+        assert_eq!(rules.cost(5, 7), 5);
+    }
+
+    #[test]
+    fn reject_invalid_rules() {
+        assert_eq!(Rules::parse("cost=1"), Err("expected `cost=<step>,<ascent>,<descent>`, found `cost=1`".to_string()));
+        assert_eq!(Rules::parse("ascent=300"), Err("`300` is not a number in `ascent=300`".to_string()));
+        assert_eq!(Rules::parse("neighbors=6"), Err("expected 4 or 8 neighbors, found `6`".to_string()));
+        assert_eq!(Rules::parse("slope=2"), Err("unknown setting `slope`".to_string()));
+        assert_eq!(Rules::parse("ascent"), Err("expected `key=value`, found `ascent`".to_string()));
+    }
+
+    #[test]
+    fn steep_rules_do_not_overflow() {
+        let rules = Rules::parse("ascent=250 descent=250").unwrap();
+        assert!(rules.allows(26, 1) && rules.allows(1, 26));
+
+        let mut heightmap = Heightmap::from(EXAMPLE).with_rules(rules);
+        assert_eq!(heightmap.climb().unwrap().0.steps(), 7);
+    }
+
+    #[test]
+    fn climb_with_other_rules() {
+        let mut heightmap = Heightmap::from(EXAMPLE).with_rules(Rules::parse("neighbors=8").unwrap());
+        let (route, _) = heightmap.climb().unwrap();
+        assert_valid(&heightmap, &route);
+        assert!(route.steps() < 31);
+
+        let mut heightmap = Heightmap::from(EXAMPLE).with_rules(Rules::parse("ascent=25").unwrap());
+        assert_eq!(heightmap.climb().unwrap().0.steps(), 7);
+
+        let ridge = "SbabcdefghijklmnopqrstuvwxyzE";
+        let mut heightmap = Heightmap::from(ridge);
+        assert_eq!(heightmap.distance_field().distance(heightmap.start), Some(28));
+        let mut heightmap = Heightmap::from(ridge).with_rules(Rules::parse("descent=0").unwrap());
+        let field = heightmap.distance_field();
+        assert_eq!(field.distance(heightmap.start), None);
+        assert_eq!(field.distance((0, 2)), Some(26));
+    }
+
+    #[test]
+    fn climb_with_costs() {
+        // Going down is expensive, so the longer route through the bottom line is cheaper.
+        let mut heightmap = Heightmap::from("ScaE\nabbb").with_rules(Rules::parse("ascent=25 cost=1,0,20").unwrap());
+        let (route, _) = heightmap.climb().unwrap();
+
+        assert_valid(&heightmap, &route);
+        assert_eq!(route.steps(), 5);
+        assert_eq!(heightmap.cost(&route), heightmap.distance_field().distance(heightmap.start).unwrap());
+        assert_eq!(heightmap.cost(&route), 5);
+    }
 }
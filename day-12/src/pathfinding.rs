@@ -0,0 +1,210 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    time::{Duration, Instant},
+};
+
+use crate::{Heightmap, Route};
+
+type Links = Vec<Vec<Option<(usize, usize)>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // Breadth-first search backwards from the end, Dijkstra when steps have different costs.
+    Explore,
+    AStar,
+    // Only for uniform costs, falls back to A* otherwise.
+    Bidirectional,
+}
+
+pub const STRATEGIES: [Strategy; 3] = [Strategy::Explore, Strategy::AStar, Strategy::Bidirectional];
+
+// Follows the links from `cell` until a cell without one, `cell` included.
+fn follow(links: &Links, cell: (usize, usize)) -> Vec<(usize, usize)> {
+    let mut path = vec![cell];
+    let mut current = cell;
+    while let Some(next) = links[current.0][current.1] {
+        path.push(next);
+        current = next;
+    }
+    path
+}
+
+struct Frontier {
+    cells: Vec<(usize, usize)>,
+    depths: Vec<Vec<Option<usize>>>,
+    links: Links,
+    depth: usize,
+}
+
+impl Frontier {
+    fn new(cell: (usize, usize), lines: usize, columns: usize) -> Self {
+        let mut depths = vec![vec![None; columns]; lines];
+        depths[cell.0][cell.1] = Some(0);
+        Frontier { cells: vec![cell], depths, links: vec![vec![None; columns]; lines], depth: 0 }
+    }
+}
+
+impl Heightmap {
+    // Route from `from` to the end, `None` when there is none.
+    pub fn find_route(&mut self, from: (usize, usize), strategy: Strategy) -> Option<Route> {
+        match strategy {
+            Strategy::Explore => {
+                self.explore(Some(from))[from.0][from.1]?;
+                Some(self.route_from(from))
+            },
+            Strategy::AStar => self.a_star(from),
+            Strategy::Bidirectional if self.rules.is_uniform() => self.bidirectional(from),
+            Strategy::Bidirectional => self.a_star(from),
+        }
+    }
+
+    // Never more than the real cost: we need at least as many steps as the distance on the grid
+    // and as it takes to climb or descend to the end altitude, and every level of the altitude
+    // difference is paid at least once.
+    fn heuristic(&self, (line, column): (usize, usize)) -> usize {
+        let lines = line.abs_diff(self.end.0);
+        let columns = column.abs_diff(self.end.1);
+        let distance = if self.rules.diagonals { lines.max(columns) } else { lines + columns };
+        let altitude = self.grid[line][column].altitude;
+        let end_altitude = self.grid[self.end.0][self.end.1].altitude;
+        let ascent = end_altitude.saturating_sub(altitude) as usize;
+        let descent = altitude.saturating_sub(end_altitude) as usize;
+        let mut steps = distance.max(ascent.div_ceil(self.rules.max_ascent.max(1) as usize));
+        if let Some(max_descent) = self.rules.max_descent {
+            steps = steps.max(descent.div_ceil(max_descent.max(1) as usize));
+        }
+        steps * self.rules.step_cost + ascent * self.rules.ascent_cost + descent * self.rules.descent_cost
+    }
+
+    fn a_star(&self, from: (usize, usize)) -> Option<Route> {
+        let mut costs = vec![vec![None; self.grid[0].len()]; self.grid.len()];
+        let mut links = vec![vec![None; self.grid[0].len()]; self.grid.len()];
+        costs[from.0][from.1] = Some(0);
+        let mut queue = BinaryHeap::from([Reverse((self.heuristic(from), 0, from))]);
+        while let Some(Reverse((_, cost, cell))) = queue.pop() {
+            if cell == self.end {
+                let mut path = follow(&links, cell);
+                path.reverse();
+                return Some(Route { path });
+            }
+            if costs[cell.0][cell.1].is_some_and(|known| known < cost) {
+                continue;
+            }
+            for next in self.successors(cell) {
+                let next_cost = cost + self.step_cost(cell, next);
+                if costs[next.0][next.1].is_some_and(|known| known <= next_cost) {
+                    continue;
+                }
+                costs[next.0][next.1] = Some(next_cost);
+                links[next.0][next.1] = Some(cell);
+                queue.push(Reverse((next_cost + self.heuristic(next), next_cost, next)));
+            }
+        }
+        None
+    }
+
+    // Adds a whole layer to `frontier`. Returns a cell also reached by `other`, which is then on
+    // a shortest route since both searches only hold complete layers.
+    fn expand(&self, frontier: &mut Frontier, other: &Frontier, forward: bool) -> Option<(usize, usize)> {
+        let mut meeting = None;
+        let mut next_cells = vec![];
+        frontier.depth += 1;
+        for cell in &frontier.cells {
+            let neighbors = if forward { self.successors(*cell) } else { self.predecessors(*cell) };
+            for neighbor in neighbors {
+                if frontier.depths[neighbor.0][neighbor.1].is_some() {
+                    continue;
+                }
+                frontier.depths[neighbor.0][neighbor.1] = Some(frontier.depth);
+                frontier.links[neighbor.0][neighbor.1] = Some(*cell);
+                next_cells.push(neighbor);
+                if meeting.is_none() && other.depths[neighbor.0][neighbor.1].is_some() {
+                    meeting = Some(neighbor);
+                }
+            }
+        }
+        frontier.cells = next_cells;
+        meeting
+    }
+
+    // Grows the smallest of the searches from both ends until they meet.
+    fn bidirectional(&self, from: (usize, usize)) -> Option<Route> {
+        if from == self.end {
+            return Some(Route { path: vec![from] });
+        }
+        let mut forward = Frontier::new(from, self.grid.len(), self.grid[0].len());
+        let mut backward = Frontier::new(self.end, self.grid.len(), self.grid[0].len());
+        loop {
+            let meeting = if forward.cells.len() <= backward.cells.len() {
+                self.expand(&mut forward, &backward, true)
+            } else {
+                self.expand(&mut backward, &forward, false)
+            };
+            if let Some(meeting) = meeting {
+                let mut path = follow(&forward.links, meeting);
+                path.reverse();
+                path.extend(follow(&backward.links, meeting).into_iter().skip(1));
+                return Some(Route { path });
+            }
+            if forward.cells.is_empty() || backward.cells.is_empty() {
+                return None;
+            }
+        }
+    }
+}
+
+// Time taken and cost found by every strategy from `from`.
+pub fn benchmark(heightmap: &mut Heightmap, from: (usize, usize)) -> Vec<(Strategy, Duration, Option<usize>)> {
+    STRATEGIES
+        .iter()
+        .map(|strategy| {
+            let started = Instant::now();
+            let route = heightmap.find_route(from, *strategy);
+            let elapsed = started.elapsed();
+            (*strategy, elapsed, route.map(|route| heightmap.cost(&route)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        generator::{GeneratorOptions, HeightmapGenerator},
+        pathfinding::STRATEGIES,
+        tests::EXAMPLE,
+        Heightmap, Rules,
+    };
+
+    // Every strategy finds a valid route with the cost of the distance field, from every cell.
+    fn cross_check(map: &str, rules: &str) {
+        let mut heightmap = Heightmap::from(map).with_rules(Rules::parse(rules).unwrap());
+        let field = heightmap.distance_field();
+        for cell in heightmap.cells().collect::<Vec<(usize, usize)>>() {
+            for strategy in STRATEGIES {
+                let route = heightmap.find_route(cell, strategy);
+                assert_eq!(route.as_ref().map(|route| heightmap.cost(route)), field.distance(cell), "{:?} from {:?} with {}", strategy, cell, rules);
+                if let Some(route) = route {
+                    assert_eq!(route.path[0], cell);
+                    crate::tests::assert_valid(&heightmap, &route);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strategies_agree_on_example() {
+        for rules in ["", "neighbors=8", "ascent=2 descent=1", "cost=1,3,1", "neighbors=8 ascent=3 cost=2,1,0"] {
+            cross_check(EXAMPLE, rules);
+        }
+    }
+
+    #[test]
+    fn strategies_agree_on_generated_maps() {
+        let options = GeneratorOptions { lines: 12, columns: 17, roughness: 3, solvable: true };
+        for (seed, rules) in ["", "neighbors=8", "descent=2", "cost=1,2,0"].into_iter().enumerate() {
+            let map = HeightmapGenerator::new(seed as u64, options).heightmap(&Rules::parse(rules).unwrap()).unwrap();
+            cross_check(&map, rules);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::cmp::Ordering;
+
+use crate::{walk, Packet, Step, Visitor};
+
+const RED: &str = "\x1b[1;31m";
+const GREEN: &str = "\x1b[1;32m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DifferenceKind {
+    Values(u32, u32),
+    LeftRanOut,
+    RightRanOut,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Wrap {
+    pub side: Side,
+    pub path: Vec<usize>,
+    pub value: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Difference {
+    pub ordering: Ordering,
+    // Path of the comparison, indices counted in wrapped lists too.
+    pub path: Vec<usize>,
+    // Element of each packet involved in the difference. A wrapped value is not descended into,
+    // and a list that ran out of items is pointed at as a whole.
+    pub left_path: Vec<usize>,
+    pub right_path: Vec<usize>,
+    pub wraps: Vec<Wrap>,
+    pub kind: DifferenceKind,
+}
+
+// What `walk` compares at one level of the path.
+#[derive(Default)]
+struct Frame {
+    // Values, or lengths of lists, of the last comparison.
+    values: Option<(u32, u32)>,
+    lengths: (usize, usize),
+    // The list on that side only exists because a value was wrapped.
+    wrap: Option<Wrap>,
+}
+
+// Follows `walk` to know where it is in both packets when it finds the first difference.
+struct Walk {
+    frames: Vec<Frame>,
+    path: Vec<usize>,
+    difference: Option<Difference>,
+}
+
+impl Walk {
+    // Indices along `path` in the packet on `side`, wrapped values are not descended into.
+    fn side_path(&self, side: Side) -> Vec<usize> {
+        self.path.iter().zip(&self.frames).filter(|(_, frame)| frame.wrap.as_ref().is_none_or(|wrap| wrap.side != side)).map(|(index, _)| *index).collect()
+    }
+
+    fn difference(&self, ordering: Ordering, kind: DifferenceKind) -> Difference {
+        let mut difference = Difference {
+            ordering,
+            path: self.path.clone(),
+            left_path: self.side_path(Side::Left),
+            right_path: self.side_path(Side::Right),
+            wraps: self.frames.iter().filter_map(|frame| frame.wrap.clone()).collect(),
+            kind,
+        };
+        // A list that ran out of items is pointed at as a whole, the other one at its extra item.
+        let frame = self.frames.last().unwrap();
+        let shortest = frame.lengths.0.min(frame.lengths.1);
+        let wrapped = |side: Side| frame.wrap.as_ref().is_some_and(|wrap| wrap.side == side);
+        match kind {
+            DifferenceKind::Values(_, _) => {},
+            DifferenceKind::LeftRanOut => {
+                difference.path.push(shortest);
+                if !wrapped(Side::Right) {
+                    difference.right_path.push(shortest);
+                }
+            },
+            DifferenceKind::RightRanOut => {
+                difference.path.push(shortest);
+                if !wrapped(Side::Left) {
+                    difference.left_path.push(shortest);
+                }
+            },
+        }
+        difference
+    }
+
+    fn wrap(&mut self, side: Side, converted: Packet) {
+        let Packet::List(items) = converted else {
+            return;
+        };
+        if let [Packet::Value(value)] = items[..] {
+            let path = self.path.clone();
+            self.frames.last_mut().unwrap().wrap = Some(Wrap { side, path, value });
+        }
+    }
+}
+
+impl Visitor for Walk {
+    fn step(&mut self, _depth: usize, step: impl FnOnce() -> Step) {
+        if self.difference.is_some() {
+            return;
+        }
+        let (ordering, kind) = match step() {
+            Step::Compare(left, right) => {
+                let frame = self.frames.last_mut().unwrap();
+                match (left, right) {
+                    (Packet::Value(l), Packet::Value(r)) => frame.values = Some((l, r)),
+                    (Packet::List(l), Packet::List(r)) => frame.lengths = (l.len(), r.len()),
+                    _ => {},
+                }
+                return;
+            },
+            Step::ConvertLeft(converted) => {
+                self.wrap(Side::Left, converted);
+                return;
+            },
+            Step::ConvertRight(converted) => {
+                self.wrap(Side::Right, converted);
+                return;
+            },
+            Step::LeftSmaller | Step::RightSmaller => {
+                let (l, r) = self.frames.last().unwrap().values.unwrap();
+                (l.cmp(&r), DifferenceKind::Values(l, r))
+            },
+            Step::LeftRanOut => (Ordering::Less, DifferenceKind::LeftRanOut),
+            Step::RightRanOut => (Ordering::Greater, DifferenceKind::RightRanOut),
+        };
+        self.difference = Some(self.difference(ordering, kind));
+    }
+
+    fn enter(&mut self, index: usize) {
+        self.path.push(index);
+        self.frames.push(Frame::default());
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+        self.frames.pop();
+    }
+}
+
+pub fn first_difference(left: &Packet, right: &Packet) -> Option<Difference> {
+    let mut visitor = Walk { frames: vec![Frame::default()], path: vec![], difference: None };
+    walk(left, right, 0, &mut visitor);
+    visitor.difference
+}
+
+pub fn format_path(path: &[usize]) -> String {
+    path.iter().map(|index| format!("[{}]", index)).collect()
+}
+
+fn mark(text: String, highlighted: bool, color: &str, ansi: bool) -> String {
+    match (highlighted, ansi) {
+        (false, _) => text,
+        (true, true) => format!("{}{}{}", color, text, RESET),
+        (true, false) => format!("*{}*", text),
+    }
+}
+
+fn inline(packet: &Packet, path: &mut Vec<usize>, target: &[usize], color: &str, ansi: bool) -> String {
+    let text = match packet {
+        Packet::Value(value) => value.to_string(),
+        Packet::List(items) => {
+            let items = items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    path.push(index);
+                    let text = inline(item, path, target, color, ansi);
+                    path.pop();
+                    text
+                })
+                .collect::<Vec<String>>();
+            format!("[{}]", items.join(","))
+        },
+    };
+    mark(text, path == target, color, ansi)
+}
+
+// One line per nested list, lists of values stay on one line. Lines are (plain, styled) so that
+// columns can be padded without counting escape codes.
+fn pretty(packet: &Packet, path: &mut Vec<usize>, target: &[usize], color: &str, depth: usize, suffix: &str, lines: &mut Vec<(String, String)>) {
+    let indent = "  ".repeat(depth);
+    match packet {
+        Packet::List(items) if items.iter().any(|item| matches!(item, Packet::List(_))) => {
+            let highlighted = path == target;
+            lines.push((format!("{}[", indent), format!("{}{}", indent, mark("[".to_string(), highlighted, color, true))));
+            for (index, item) in items.iter().enumerate() {
+                path.push(index);
+                pretty(item, path, target, color, depth + 1, if index + 1 < items.len() { "," } else { "" }, lines);
+                path.pop();
+            }
+            let close = format!("]{}", suffix);
+            lines.push((format!("{}{}", indent, close), format!("{}{}", indent, mark(close.clone(), highlighted, color, true))));
+        },
+        _ => {
+            let plain = inline(packet, &mut path.clone(), &[usize::MAX], color, false);
+            let styled = inline(packet, path, target, color, true);
+            lines.push((format!("{}{}{}", indent, plain, suffix), format!("{}{}{}", indent, styled, suffix)));
+        },
+    }
+}
+
+fn describe(difference: &Difference) -> String {
+    let verdict = if difference.ordering == Ordering::Less { "in the right order" } else { "not in the right order" };
+    let detail = match difference.kind {
+        DifferenceKind::Values(l, r) => format!("left {} vs right {}", l, r),
+        DifferenceKind::LeftRanOut => "left side ran out of items".to_string(),
+        DifferenceKind::RightRanOut => "right side ran out of items".to_string(),
+    };
+    let mut lines = vec![format!("First difference at {}: {}, {}", format_path(&difference.path), detail, verdict)];
+    for wrap in &difference.wraps {
+        let side = if wrap.side == Side::Left { "Left" } else { "Right" };
+        lines.push(format!("{} value {} at {} wrapped into [{}]", side, wrap.value, format_path(&wrap.path), wrap.value));
+    }
+    lines.join("\n")
+}
+
+// Plain text marks the differing elements with `*`; with `ansi` they are coloured and both
+// packets are shown side by side.
+pub fn render_diff(left: &Packet, right: &Packet, ansi: bool) -> String {
+    let Some(difference) = first_difference(left, right) else {
+        return "Packets are equal".to_string();
+    };
+    if !ansi {
+        return format!(
+            "{}\nleft:  {}\nright: {}",
+            describe(&difference),
+            inline(left, &mut vec![], &difference.left_path, RED, false),
+            inline(right, &mut vec![], &difference.right_path, GREEN, false)
+        );
+    }
+    let mut left_lines = vec![];
+    let mut right_lines = vec![];
+    pretty(left, &mut vec![], &difference.left_path, RED, 0, "", &mut left_lines);
+    pretty(right, &mut vec![], &difference.right_path, GREEN, 0, "", &mut right_lines);
+    let width = left_lines.iter().map(|(plain, _)| plain.len()).max().unwrap_or(0).max(4);
+    let mut lines = vec![describe(&difference), format!("{:width$} | right", "left", width = width)];
+    for row in 0..left_lines.len().max(right_lines.len()) {
+        let (left_plain, left_styled) = left_lines.get(row).cloned().unwrap_or_default();
+        let (_, right_styled) = right_lines.get(row).cloned().unwrap_or_default();
+        lines.push(format!("{}{} | {}", left_styled, " ".repeat(width - left_plain.len()), right_styled));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::{
+        diff::{first_difference, render_diff, DifferenceKind, Side, Wrap},
+        Packet,
+    };
+
+    #[test]
+    fn difference_inside_wrapped_value() {
+        let difference = first_difference(&Packet::from("[[1],[2,3,4]]"), &Packet::from("[[1],4]")).unwrap();
+
+        assert_eq!(difference.ordering, Ordering::Less);
+        assert_eq!(difference.path, vec![1, 0]);
+        assert_eq!(difference.left_path, vec![1, 0]);
+        assert_eq!(difference.right_path, vec![1]);
+        assert_eq!(difference.wraps, vec![Wrap { side: Side::Right, path: vec![1], value: 4 }]);
+        assert_eq!(difference.kind, DifferenceKind::Values(2, 4));
+    }
+
+    #[test]
+    fn difference_when_running_out() {
+        let difference = first_difference(&Packet::from("[[[]]]"), &Packet::from("[[]]")).unwrap();
+
+        assert_eq!(difference.path, vec![0, 0]);
+        assert_eq!(difference.left_path, vec![0, 0]);
+        assert_eq!(difference.right_path, vec![0]);
+        assert_eq!(difference.kind, DifferenceKind::RightRanOut);
+        assert_eq!(first_difference(&Packet::from("[[1]]"), &Packet::from("[1]")), None);
+    }
+
+    #[test]
+    fn render_plain_diff() {
+        let rendered = render_diff(&Packet::from("[1,[2,[3,[4,[5,6,7]]]],8,9]"), &Packet::from("[1,[2,[3,[4,[5,6,0]]]],8,9]"), false);
+
+        assert_eq!(
+            rendered,
+            [
+                "First difference at [1][1][1][1][2]: left 7 vs right 0, not in the right order",
+                "left:  [1,[2,[3,[4,[5,6,*7*]]]],8,9]",
+                "right: [1,[2,[3,[4,[5,6,*0*]]]],8,9]",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn render_side_by_side_diff() {
+        let rendered = render_diff(&Packet::from("[[1],[2,3,4]]"), &Packet::from("[[1],4]"), true);
+
+        assert_eq!(
+            rendered,
+            [
+                "First difference at [1][0]: left 2 vs right 4, in the right order",
+                "Right value 4 at [1] wrapped into [4]",
+                "left      | right",
+                "[         | [",
+                "  [1],    |   [1],",
+                "  [\x1b[1;31m2\x1b[0m,3,4] |   \x1b[1;32m4\x1b[0m",
+                "]         | ]",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    env,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::Packet;
+
+static SORTS: AtomicUsize = AtomicUsize::new(0);
+
+pub struct SortOptions {
+    // Rough budget for the packets held in memory before a run is written out.
+    pub memory_limit: usize,
+    pub dedup: bool,
+    pub temp_dir: PathBuf,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions { memory_limit: 256 * 1024 * 1024, dedup: false, temp_dir: env::temp_dir() }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SortStats {
+    pub read: usize,
+    pub runs: usize,
+    pub written: usize,
+}
+
+// Packets that compare equal keep a stable order through their text, so that exact duplicates end
+// up next to each other.
+type Entry = (Packet, String);
+
+// `number` is 1-based, to point at the malformed line.
+fn parse_line(line: String, number: usize) -> io::Result<Option<Entry>> {
+    let line = line.trim().to_string();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match Packet::parse(&line) {
+        Some(packet) => Ok(Some((packet, line))),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("line {}: invalid packet `{}`", number, line))),
+    }
+}
+
+// Temporary directory of the runs, removed with them whether the sort succeeds or not.
+struct RunDirectory(PathBuf);
+
+impl Drop for RunDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// A parsed packet takes a lot more room than its text, mostly in nested `Vec`s.
+fn estimated_size(line: &str) -> usize {
+    line.len() * 8 + 64
+}
+
+fn write_sorted(entries: &mut Vec<Entry>, dedup: bool, mut output: impl Write) -> io::Result<usize> {
+    entries.sort();
+    if dedup {
+        entries.dedup_by(|a, b| a.1 == b.1);
+    }
+    for (_, line) in entries.iter() {
+        writeln!(output, "{}", line)?;
+    }
+    output.flush()?;
+    Ok(entries.len())
+}
+
+// Sorts packet lines in protocol order: sorted runs go to temporary files whenever the memory
+// limit is reached, then all runs are merged into `output`.
+pub fn sort(input: impl BufRead, output: impl Write, options: &SortOptions) -> io::Result<SortStats> {
+    let directory = RunDirectory(options.temp_dir.join(format!("day-13-sort-{}-{}", process::id(), SORTS.fetch_add(1, Ordering::SeqCst))));
+    let mut runs = vec![];
+    let mut entries = vec![];
+    let mut size = 0;
+    let mut read = 0;
+    for (index, line) in input.lines().enumerate() {
+        let Some(entry) = parse_line(line?, index + 1)? else {
+            continue;
+        };
+        read += 1;
+        size += estimated_size(&entry.1);
+        entries.push(entry);
+        if size >= options.memory_limit {
+            fs::create_dir_all(&directory.0)?;
+            let run = directory.0.join(format!("run-{}", runs.len()));
+            write_sorted(&mut entries, options.dedup, BufWriter::new(File::create(&run)?))?;
+            runs.push(run);
+            entries.clear();
+            size = 0;
+        }
+    }
+
+    if runs.is_empty() {
+        let written = write_sorted(&mut entries, options.dedup, output)?;
+        return Ok(SortStats { read, runs: 1, written });
+    }
+    if !entries.is_empty() {
+        let run = directory.0.join(format!("run-{}", runs.len()));
+        write_sorted(&mut entries, options.dedup, BufWriter::new(File::create(&run)?))?;
+        runs.push(run);
+    }
+
+    let written = merge(&runs, options.dedup, output)?;
+    Ok(SortStats { read, runs: runs.len(), written })
+}
+
+fn next_entry(reader: &mut impl Iterator<Item = (usize, io::Result<String>)>) -> io::Result<Option<Entry>> {
+    for (index, line) in reader {
+        if let Some(entry) = parse_line(line?, index + 1)? {
+            return Ok(Some(entry));
+        }
+    }
+    Ok(None)
+}
+
+fn merge(runs: &[PathBuf], dedup: bool, output: impl Write) -> io::Result<usize> {
+    let mut output = BufWriter::new(output);
+    let mut readers = runs
+        .iter()
+        .map(|run| Ok(BufReader::new(File::open(run)?).lines().enumerate()))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (index, reader) in readers.iter_mut().enumerate() {
+        if let Some(entry) = next_entry(reader)? {
+            heap.push(Reverse((entry, index)));
+        }
+    }
+    let mut written = 0;
+    let mut last: Option<String> = None;
+    while let Some(Reverse((entry, index))) = heap.pop() {
+        if let Some(next) = next_entry(&mut readers[index])? {
+            heap.push(Reverse((next, index)));
+        }
+        if dedup && last.as_deref() == Some(entry.1.as_str()) {
+            continue;
+        }
+        writeln!(output, "{}", entry.1)?;
+        written += 1;
+        last = Some(entry.1);
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        external_sort::{sort, SortOptions, SortStats},
+        Packet,
+    };
+
+    const INPUT: &str = "[1,1,3,1,1]\n[1,1,5,1,1]\n\n[[1],[2,3,4]]\n[[1],4]\n\n[9]\n[[8,7,6]]\n\n[[4,4],4,4]\n[[4,4],4,4,4]\n\n[7,7,7,7]\n[7,7,7]\n\n[]\n[3]\n\n[[[]]]\n[[]]\n\n[1,[2,[3,[4,[5,6,7]]]],8,9]\n[1,[2,[3,[4,[5,6,0]]]],8,9]\n[9]\n[[1]]\n[1]\n";
+
+    fn sorted_in_memory() -> Vec<String> {
+        let mut entries = INPUT.lines().filter(|line| !line.is_empty()).map(|line| (Packet::from(line), line.to_string())).collect::<Vec<(Packet, String)>>();
+        entries.sort();
+        entries.into_iter().map(|(_, line)| line).collect()
+    }
+
+    fn lines(output: Vec<u8>) -> Vec<String> {
+        String::from_utf8(output).unwrap().lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn sort_with_several_runs() {
+        let options = SortOptions { memory_limit: 300, ..SortOptions::default() };
+        let mut output = vec![];
+        let stats = sort(INPUT.as_bytes(), &mut output, &options).unwrap();
+
+        assert_eq!(stats.read, 19);
+        assert!(stats.runs > 1);
+        assert_eq!(lines(output), sorted_in_memory());
+    }
+
+    #[test]
+    fn sort_with_dedup() {
+        let options = SortOptions { memory_limit: 300, dedup: true, ..SortOptions::default() };
+        let mut output = vec![];
+        let stats = sort(INPUT.as_bytes(), &mut output, &options).unwrap();
+        let mut expected = sorted_in_memory();
+        expected.dedup();
+
+        assert_eq!(stats.written, 18);
+        assert_eq!(lines(output), expected);
+    }
+
+    #[test]
+    fn sort_in_a_single_run() {
+        let mut output = vec![];
+        let stats = sort(INPUT.as_bytes(), &mut output, &SortOptions::default()).unwrap();
+
+        assert_eq!(stats, SortStats { read: 19, runs: 1, written: 19 });
+        assert_eq!(lines(output), sorted_in_memory());
+    }
+
+    #[test]
+    fn report_malformed_lines_and_clean_up() {
+        let temp_dir = std::env::temp_dir().join(format!("day-13-sort-test-{}", std::process::id()));
+        let options = SortOptions { memory_limit: 300, temp_dir: temp_dir.clone(), ..SortOptions::default() };
+        let input = format!("{}[1,\n[2]\n", INPUT);
+        let error = sort(input.as_bytes(), &mut vec![], &options).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 27: invalid packet `[1,`");
+        assert_eq!(std::fs::read_dir(&temp_dir).map_or(0, |entries| entries.count()), 0);
+        let _ = std::fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::Packet;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GeneratorOptions {
+    pub max_depth: usize,
+    pub max_width: usize,
+    pub max_value: u32,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        GeneratorOptions { max_depth: 4, max_width: 5, max_value: 10 }
+    }
+}
+
+pub struct PacketGenerator {
+    rng: StdRng,
+    options: GeneratorOptions,
+}
+
+impl PacketGenerator {
+    pub fn new(seed: u64, options: GeneratorOptions) -> Self {
+        PacketGenerator { rng: StdRng::seed_from_u64(seed), options }
+    }
+
+    // Packets are always lists at the top level, like in the puzzle input.
+    pub fn packet(&mut self) -> Packet {
+        self.list(0)
+    }
+
+    fn list(&mut self, depth: usize) -> Packet {
+        let width = self.rng.gen_range(0..=self.options.max_width);
+        Packet::List((0..width).map(|_| self.item(depth + 1)).collect())
+    }
+
+    fn item(&mut self, depth: usize) -> Packet {
+        if depth < self.options.max_depth && self.rng.gen_range(0..3) == 0 {
+            self.list(depth)
+        } else {
+            Packet::Value(self.rng.gen_range(0..=self.options.max_value))
+        }
+    }
+
+    // Pairs separated by blank lines, as in the puzzle input.
+    pub fn write_pairs(&mut self, pairs: usize, mut output: impl Write) -> io::Result<()> {
+        for index in 0..pairs {
+            if index > 0 {
+                writeln!(output)?;
+            }
+            writeln!(output, "{}\n{}", self.packet(), self.packet())?;
+        }
+        output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use crate::{
+        diff::first_difference,
+        explain,
+        generator::{GeneratorOptions, PacketGenerator},
+        Packet, Packets,
+    };
+
+    const CASES: usize = 5000;
+
+    fn generator(seed: u64) -> PacketGenerator {
+        PacketGenerator::new(seed, GeneratorOptions { max_depth: 3, max_width: 3, max_value: 3 })
+    }
+
+    #[test]
+    fn same_seed_same_packets() {
+        let (mut first, mut second) = (generator(7), generator(7));
+        for _ in 0..100 {
+            assert_eq!(first.packet().to_string(), second.packet().to_string());
+        }
+    }
+
+    #[test]
+    fn compare_is_antisymmetric() {
+        let mut generator = generator(1);
+        for _ in 0..CASES {
+            let (a, b) = (generator.packet(), generator.packet());
+
+            assert_eq!(a.cmp(&b), b.cmp(&a).reverse(), "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn compare_is_transitive() {
+        let mut generator = generator(2);
+        for _ in 0..CASES {
+            let (a, b, c) = (generator.packet(), generator.packet(), generator.packet());
+            if a <= b && b <= c {
+                assert!(a <= c, "{} {} {}", a, b, c);
+            }
+        }
+    }
+
+    #[test]
+    fn compare_agrees_with_right_order_explain_and_diff() {
+        let mut generator = generator(3);
+        for _ in 0..CASES {
+            let (left, right) = (generator.packet(), generator.packet());
+            let ordering = left.cmp(&right);
+
+            assert_eq!(explain(&left, &right).0, ordering);
+            assert_eq!(first_difference(&left, &right).map_or(Ordering::Equal, |difference| difference.ordering), ordering);
+            assert_eq!(Packets { left, right }.is_in_right_order(), ordering != Ordering::Greater);
+        }
+    }
+
+    #[test]
+    fn generated_packets_round_trip() {
+        let mut generator = generator(4);
+        for _ in 0..CASES {
+            let packet = generator.packet();
+
+            assert_eq!(Packet::from(packet.to_string().as_str()).to_string(), packet.to_string());
+        }
+    }
+
+    #[test]
+    fn write_pairs_in_puzzle_format() {
+        let mut output = vec![];
+        generator(5).write_pairs(3, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(output.split("\n\n").map(Packets::from).count(), 3);
+    }
+}
@@ -1,102 +1,322 @@
-use std::{fs, cmp::Ordering};
-use serde::Deserialize;
-use serde_json::{Value};
+use std::{
+    cmp::Ordering,
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, IsTerminal},
+};
 
-#[derive(Debug, PartialEq, Deserialize)]
-enum Data {
-    List(Vec<Data>),
+mod diff;
+mod external_sort;
+mod generator;
+
+#[derive(Debug, Clone)]
+enum Packet {
+    List(Vec<Packet>),
     Value(u32),
 }
 
-#[derive(Debug, PartialEq)]
-struct Packets {
-    left: Value,
-    right: Value,
+fn parse_packet(input: &[u8], position: &mut usize) -> Option<Packet> {
+    match input.get(*position)? {
+        b'[' => {
+            *position += 1;
+            let mut items = vec![];
+            if input.get(*position) == Some(&b']') {
+                *position += 1;
+                return Some(Packet::List(items));
+            }
+            loop {
+                items.push(parse_packet(input, position)?);
+                match input.get(*position)? {
+                    b',' => *position += 1,
+                    b']' => {
+                        *position += 1;
+                        return Some(Packet::List(items));
+                    },
+                    _ => return None,
+                }
+            }
+        },
+        b'0'..=b'9' => {
+            let start = *position;
+            while input.get(*position).is_some_and(|c| c.is_ascii_digit()) {
+                *position += 1;
+            }
+            std::str::from_utf8(&input[start..*position]).ok()?.parse::<u32>().ok().map(Packet::Value)
+        },
+        _ => None,
+    }
 }
 
-impl From<&str> for Packets {
+impl Packet {
+    fn parse(input: &str) -> Option<Packet> {
+        let input = input.trim().as_bytes();
+        let mut position = 0;
+        let packet = parse_packet(input, &mut position)?;
+        if position == input.len() {
+            Some(packet)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<&str> for Packet {
     fn from(input: &str) -> Self {
-        let split = input.split("\n").collect::<Vec<&str>>();
-        Packets { left: serde_json::from_str(split[0]).unwrap(), right: serde_json::from_str(split[1]).unwrap() }
+        Packet::parse(input).unwrap_or_else(|| panic!("Invalid packet: {}", input))
     }
 }
 
-impl Packets {
-    fn is_in_right_order(&self) -> bool {
-        in_right_order(&self.left, &self.right).unwrap_or(true)
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Value(value) => write!(f, "{}", value),
+            Packet::List(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Step {
+    Compare(Packet, Packet),
+    ConvertLeft(Packet),
+    ConvertRight(Packet),
+    LeftSmaller,
+    RightSmaller,
+    LeftRanOut,
+    RightRanOut,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct TraceLine {
+    depth: usize,
+    step: Step,
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}- ", "  ".repeat(self.depth))?;
+        match &self.step {
+            Step::Compare(left, right) => write!(f, "Compare {} vs {}", left, right),
+            Step::ConvertLeft(left) => write!(f, "Mixed types; convert left to {} and retry comparison", left),
+            Step::ConvertRight(right) => write!(f, "Mixed types; convert right to {} and retry comparison", right),
+            Step::LeftSmaller => write!(f, "Left side is smaller, so inputs are in the right order"),
+            Step::RightSmaller => write!(f, "Right side is smaller, so inputs are not in the right order"),
+            Step::LeftRanOut => write!(f, "Left side ran out of items, so inputs are in the right order"),
+            Step::RightRanOut => write!(f, "Right side ran out of items, so inputs are not in the right order"),
+        }
     }
 }
 
-fn in_right_order(left: &Value, right: &Value) -> Option<bool> {
+// Told about every step of `walk`, in order. Steps are only built by the visitors that need them.
+trait Visitor {
+    fn step(&mut self, _depth: usize, _step: impl FnOnce() -> Step) {}
+
+    // `walk` compares the items at `index` in both lists, until `leave`.
+    fn enter(&mut self, _index: usize) {}
+
+    fn leave(&mut self) {}
+}
+
+impl Visitor for () {}
+
+impl Visitor for Vec<TraceLine> {
+    fn step(&mut self, depth: usize, step: impl FnOnce() -> Step) {
+        self.push(TraceLine { depth, step: step() });
+    }
+}
+
+// Lists compare item by item then by length. A value compared to a list is first wrapped into a
+// list. Each step goes to `visitor`, with the indentation of the puzzle text.
+fn walk(left: &Packet, right: &Packet, depth: usize, visitor: &mut impl Visitor) -> Ordering {
+    visitor.step(depth, || Step::Compare(left.clone(), right.clone()));
     match (left, right) {
-        (Value::Number(l), Value::Number(r)) => {
-            if l.as_u64() == r.as_u64() {
-                None
-            } else {
-                Some(l.as_u64() <= r.as_u64())
+        (Packet::Value(l), Packet::Value(r)) => {
+            let ordering = l.cmp(r);
+            match ordering {
+                Ordering::Less => visitor.step(depth + 1, || Step::LeftSmaller),
+                Ordering::Greater => visitor.step(depth + 1, || Step::RightSmaller),
+                Ordering::Equal => {},
             }
+            ordering
         },
-        (Value::Array(array_left), Value::Array(array_right)) => {
-            for (l, r) in array_left.iter().zip(array_right.iter()) {
-                let result = in_right_order(l, r);
-                if result.is_some() {
-                    return result;
+        (Packet::List(l), Packet::List(r)) => {
+            for (index, (left_item, right_item)) in l.iter().zip(r).enumerate() {
+                visitor.enter(index);
+                let ordering = walk(left_item, right_item, depth + 1, visitor);
+                visitor.leave();
+                if ordering != Ordering::Equal {
+                    return ordering;
                 }
             }
-            if array_left.len() == array_right.len() {
-                None
-            } else {
-                Some(array_left.len() < array_right.len())
+            let ordering = l.len().cmp(&r.len());
+            match ordering {
+                Ordering::Less => visitor.step(depth + 1, || Step::LeftRanOut),
+                Ordering::Greater => visitor.step(depth + 1, || Step::RightRanOut),
+                Ordering::Equal => {},
             }
-            
+            ordering
         },
-        (l, r) if l.is_u64() && r.is_array() => {
-            let array_left = Value::Array(vec![l.clone()]);
-            in_right_order(&array_left, r)
+        (Packet::Value(l), Packet::List(_)) => {
+            let converted = Packet::List(vec![Packet::Value(*l)]);
+            visitor.step(depth + 1, || Step::ConvertLeft(converted.clone()));
+            walk(&converted, right, depth + 1, visitor)
         },
-        (l, r) if l.is_array() && r.is_u64() => {
-            let array_right = Value::Array(vec![r.clone()]);
-            in_right_order(l, &array_right)
-        }
-        _ => unreachable!(),
+        (Packet::List(_), Packet::Value(r)) => {
+            let converted = Packet::List(vec![Packet::Value(*r)]);
+            visitor.step(depth + 1, || Step::ConvertRight(converted.clone()));
+            walk(left, &converted, depth + 1, visitor)
+        },
+    }
+}
+
+fn compare(left: &Packet, right: &Packet) -> Ordering {
+    walk(left, right, 0, &mut ())
+}
+
+fn explain(left: &Packet, right: &Packet) -> (Ordering, Vec<TraceLine>) {
+    let mut trace = vec![];
+    let ordering = walk(left, right, 0, &mut trace);
+    (ordering, trace)
+}
+
+// Equality has to agree with the ordering, so `[[1]]` and `1` are equal packets.
+impl PartialEq for Packet {
+    fn eq(&self, other: &Self) -> bool {
+        compare(self, other) == Ordering::Equal
+    }
+}
+
+impl Eq for Packet {}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(self, other)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Packets {
+    left: Packet,
+    right: Packet,
+}
+
+impl From<&str> for Packets {
+    fn from(input: &str) -> Self {
+        let split = input.split('\n').collect::<Vec<&str>>();
+        Packets { left: Packet::from(split[0]), right: Packet::from(split[1]) }
     }
 }
 
-fn compare(a: &Value, b: &Value) -> Ordering {
-    match in_right_order(a, b) {
-        None => Ordering::Equal,
-        Some(true) => Ordering::Less,
-        Some(false) => Ordering::Greater,
+impl Packets {
+    fn is_in_right_order(&self) -> bool {
+        self.left <= self.right
     }
 }
 
+fn packets_from_reader(reader: impl BufRead) -> impl Iterator<Item = Packet> {
+    reader.lines().map(|line| line.unwrap()).filter(|line| !line.trim().is_empty()).map(|line| Packet::from(line.as_str()))
+}
+
+// 1-based rank of each marker once inserted among the packets and sorted, without keeping the
+// packets around: a marker is preceded by every smaller packet and every smaller marker. Equal
+// markers keep their order, as in a stable sort.
+fn marker_ranks(packets: impl Iterator<Item = Packet>, markers: &[Packet]) -> Vec<usize> {
+    let mut ranks = markers
+        .iter()
+        .enumerate()
+        .map(|(index, marker)| 1 + markers.iter().enumerate().filter(|(other_index, other)| *other < marker || (*other == marker && *other_index < index)).count())
+        .collect::<Vec<usize>>();
+    for packet in packets {
+        for (rank, marker) in ranks.iter_mut().zip(markers) {
+            if packet < *marker {
+                *rank += 1;
+            }
+        }
+    }
+    ranks
+}
+
 fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() >= 4 && args[1] == "sort" {
+        let mut options = external_sort::SortOptions { dedup: args.iter().any(|arg| arg == "dedup"), ..Default::default() };
+        if let Some(limit) = args.get(4).and_then(|limit| limit.parse::<usize>().ok()) {
+            options.memory_limit = limit;
+        }
+        let input = BufReader::new(File::open(&args[2]).expect("oops"));
+        let output = File::create(&args[3]).expect("oops");
+        match external_sort::sort(input, output, &options) {
+            Ok(stats) => println!("{} packets read, {} runs, {} written", stats.read, stats.runs, stats.written),
+            Err(error) => println!("{}: {}", args[2], error),
+        }
+        return;
+    }
+
+    if args.len() >= 3 && args[1] == "generate" {
+        let numbers = args[2..].iter().map(|arg| arg.parse::<usize>().unwrap()).collect::<Vec<usize>>();
+        let mut options = generator::GeneratorOptions::default();
+        if numbers.len() >= 5 {
+            options = generator::GeneratorOptions { max_depth: numbers[2], max_width: numbers[3], max_value: numbers[4] as u32 };
+        }
+        let seed = numbers.get(1).copied().unwrap_or(0) as u64;
+        let stdout = io::stdout();
+        generator::PacketGenerator::new(seed, options).write_pairs(numbers[0], stdout.lock()).expect("oops");
+        return;
+    }
+
     let file_name = "input/day-13";
     let file_content = fs::read_to_string(file_name).expect("oops");
 
-    let packets = file_content.split("\n\n").map(|input| Packets::from(input)).collect::<Vec<Packets>>();
+    let packets = file_content.trim().split("\n\n").map(Packets::from).collect::<Vec<Packets>>();
+    if args.get(1).map(|arg| arg.as_str()) == Some("diff") {
+        // Colours and columns only make sense on a terminal, `plain` asks for text anyway.
+        let ansi = io::stdout().is_terminal() && args.get(2).map(|arg| arg.as_str()) != Some("plain");
+        for (index, pair) in packets.iter().enumerate().filter(|(_, pair)| !pair.is_in_right_order()) {
+            println!("== Pair {} ==", index + 1);
+            println!("{}", diff::render_diff(&pair.left, &pair.right, ansi));
+            println!();
+        }
+        return;
+    }
+    if args.get(1).map(|arg| arg.as_str()) == Some("explain") {
+        for (index, pair) in packets.iter().enumerate() {
+            println!("== Pair {} ==", index + 1);
+            for line in explain(&pair.left, &pair.right).1 {
+                println!("{}", line);
+            }
+            println!();
+        }
+        return;
+    }
     let sum_in_order = packets.iter().enumerate().filter(|(_, packet)| packet.is_in_right_order()).map(|(index, _)| {println!("{}", index + 1);index + 1}).sum::<usize>();
     println!("Sum of indices in order: {}", sum_in_order);
 
-    let divider_2: Value = serde_json::from_str("[[2]]").unwrap();
-    let divider_6: Value = serde_json::from_str("[[6]]").unwrap();
-    let mut all_packets: Vec<Value> = vec![divider_2.clone(), divider_6.clone()];
-    for packet in packets {
-        all_packets.push(packet.left);
-        all_packets.push(packet.right);
-    }
-    all_packets.sort_by(|a,b| compare(a, b));
-    println!("{:?}", all_packets);
-
-    let position_2 = all_packets.iter().position(|packet| *packet == divider_2).unwrap() + 1;
-    let position_6 = all_packets.iter().position(|packet| *packet == divider_6).unwrap() + 1;
+    let reader = BufReader::new(File::open(file_name).expect("oops"));
+    let ranks = marker_ranks(packets_from_reader(reader), &[Packet::from("[[2]]"), Packet::from("[[6]]")]);
+    let (position_2, position_6) = (ranks[0], ranks[1]);
     println!("Positions: {} {}", position_2, position_6);
     println!("Product of positions: {}", position_2 * position_6);
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Packets;
+    use crate::{explain, marker_ranks, packets_from_reader, Packet, Packets, Step, TraceLine};
+    use std::cmp::Ordering;
 
     #[test]
     fn compare_simple_arrays_in_order() {
@@ -177,4 +397,84 @@ mod tests {
 
         assert!(!packets.is_in_right_order());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn compare_mixed_types_is_total() {
+        assert_eq!(Packet::from("[[1]]"), Packet::from("[1]"));
+        assert!(Packet::from("[[1],2]") < Packet::from("[1,3]"));
+        assert!(Packet::from("[]") < Packet::from("[[]]"));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let input = "[1,[2,[3,[4,[5,6,7]]]],8,9,[],[[]],10]";
+
+        assert_eq!(Packet::from(input).to_string(), input);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_packets() {
+        assert_eq!(Packet::parse("[1,2"), None);
+        assert_eq!(Packet::parse("[1,,2]"), None);
+        assert_eq!(Packet::parse("[1]]"), None);
+        assert_eq!(Packet::parse("[a]"), None);
+    }
+
+    #[test]
+    fn sort_example_packets() {
+        let mut packets = ["[1,1,3,1,1]", "[[6]]", "[]", "[[2]]", "[[[]]]", "[9]", "[[1],4]", "[3]"]
+            .iter()
+            .map(|input| Packet::from(*input))
+            .collect::<Vec<Packet>>();
+        packets.sort();
+
+        assert_eq!(
+            packets.iter().map(|packet| packet.to_string()).collect::<Vec<String>>(),
+            vec!["[]", "[[[]]]", "[1,1,3,1,1]", "[[1],4]", "[[2]]", "[3]", "[[6]]", "[9]"]
+        );
+    }
+
+    #[test]
+    fn explain_mixed_types() {
+        let (ordering, trace) = explain(&Packet::from("[[1],[2,3,4]]"), &Packet::from("[[1],4]"));
+
+        assert_eq!(ordering, Ordering::Less);
+        assert_eq!(
+            trace.iter().map(|line| line.to_string()).collect::<Vec<String>>().join("\n"),
+            [
+                "- Compare [[1],[2,3,4]] vs [[1],4]",
+                "  - Compare [1] vs [1]",
+                "    - Compare 1 vs 1",
+                "  - Compare [2,3,4] vs 4",
+                "    - Mixed types; convert right to [4] and retry comparison",
+                "    - Compare [2,3,4] vs [4]",
+                "      - Compare 2 vs 4",
+                "        - Left side is smaller, so inputs are in the right order",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn explain_run_out_of_items() {
+        let (ordering, trace) = explain(&Packet::from("[7,7,7,7]"), &Packet::from("[7,7,7]"));
+
+        assert_eq!(ordering, Ordering::Greater);
+        assert_eq!(trace.len(), 5);
+        assert_eq!(trace.last(), Some(&TraceLine { depth: 1, step: Step::RightRanOut }));
+
+        let (_, trace) = explain(&Packet::from("[]"), &Packet::from("[3]"));
+
+        assert_eq!(trace.last(), Some(&TraceLine { depth: 1, step: Step::LeftRanOut }));
+    }
+
+    #[test]
+    fn marker_ranks_match_sorted_positions() {
+        let input = "[1,1,3,1,1]\n[1,1,5,1,1]\n\n[[1],[2,3,4]]\n[[1],4]\n\n[9]\n[[8,7,6]]\n\n[[4,4],4,4]\n[[4,4],4,4,4]\n\n[7,7,7,7]\n[7,7,7]\n\n[]\n[3]\n\n[[[]]]\n[[]]\n\n[1,[2,[3,[4,[5,6,7]]]],8,9]\n[1,[2,[3,[4,[5,6,0]]]],8,9]\n";
+        let markers = [Packet::from("[[6]]"), Packet::from("[[2]]")];
+
+        assert_eq!(marker_ranks(packets_from_reader(input.as_bytes()), &markers), vec![14, 10]);
+        assert_eq!(marker_ranks(packets_from_reader(input.as_bytes()), &[Packet::from("[[2]]"), Packet::from("[[2]]")]), vec![10, 11]);
+        assert_eq!(marker_ranks(packets_from_reader(input.as_bytes()), &[Packet::from("[[2]]"), Packet::from("[2]"), Packet::from("[[2]]")]), vec![10, 11, 12]);
+    }
+}
@@ -1,4 +1,8 @@
-use std::{fs::File, io::{BufReader, BufRead}};
+use std::{cmp::Reverse, collections::{HashMap, HashSet}, env, fs::File, io::{self, BufReader, BufRead}, time::Duration};
+
+mod render;
+mod repl;
+mod report;
 
 #[derive(Debug, PartialEq)]
 struct Path {
@@ -42,149 +46,370 @@ impl Path {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum Cell {
     Empty,
     Rock,
     Sand,
 }
 
-impl Cell {
-    fn new() -> Self {
-        Cell::Empty
+#[derive(Debug, PartialEq)]
+struct Edit {
+    displaced: usize,
+    moved: usize,
+    lost: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum SandOutcome {
+    Rest((usize, usize)),
+    Abyss,
+    Blocked,
+}
+
+const SOURCE: (usize, usize) = (500, 0);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Source {
+    point: (usize, usize),
+    weight: usize,
+}
+
+// `x,y` or `x,y*weight`
+impl From<&str> for Source {
+    fn from(input: &str) -> Self {
+        let (point, weight) = input.split_once('*').unwrap_or((input, "1"));
+        let (x, y) = point.split_once(',').unwrap();
+        Source { point: (x.parse::<usize>().unwrap(), y.parse::<usize>().unwrap()), weight: weight.parse::<usize>().unwrap() }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Emission {
+    RoundRobin,
+    // Smooth weighted round robin: each source emits in proportion to its weight, interleaved.
+    Weighted,
+}
+
+// Moves tried in order from the current position, as (dx, dy) with y going down.
+#[derive(Debug, PartialEq, Clone)]
+struct Rules {
+    moves: Vec<(isize, isize)>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { moves: vec![(0, 1), (-1, 1), (1, 1)] }
+    }
+}
+
+impl From<&str> for Rules {
+    fn from(input: &str) -> Self {
+        let moves = input
+            .split_whitespace()
+            .map(|pair| {
+                let (dx, dy) = pair.split_once(',').unwrap();
+                (dx.parse::<isize>().unwrap(), dy.parse::<isize>().unwrap())
+            })
+            .collect::<Vec<(isize, isize)>>();
+        Rules { moves }
+    }
+}
+
+impl Rules {
+    // Sand may also slide diagonally up to `reach` cells sideways, closest first.
+    fn sliding(reach: isize) -> Self {
+        let mut moves = vec![(0, 1)];
+        for distance in 1..=reach {
+            moves.push((-distance, 1));
+            moves.push((distance, 1));
+        }
+        Rules { moves }
+    }
+
+    // Falls straight down, otherwise spreads sideways. On a floor, it flows away past the rocks.
+    fn water() -> Self {
+        Rules { moves: vec![(0, 1), (-1, 0), (1, 0)] }
     }
 }
 
+// Only rock and sand are stored, so the cave grows as far as the sand goes.
+// `paths` holds the fall path of the last grain of each source: the next one follows it until the
+// last open position.
 #[derive(Debug)]
 struct Cave {
-    offset: usize,
-    width: usize,
-    height: usize,
-    cells: Vec<Vec<Cell>>,
+    cells: HashMap<(usize, usize), Cell>,
+    lowest_rock: usize,
+    floor: Option<usize>,
+    rules: Rules,
+    sources: Vec<Source>,
+    emission: Emission,
+    credits: Vec<isize>,
+    next_source: usize,
+    last_source: usize,
+    paths: Vec<Vec<(usize, usize)>>,
 }
 
 impl From<&Vec<Path>> for Cave {
     fn from(paths: &Vec<Path>) -> Self {
-        let offset = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.0).min().unwrap()
-        }).min().unwrap();
-        
-        let width = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.0).max().unwrap()
-        }).max().unwrap() - offset + 1;
-    
-        let height = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.1).max().unwrap()
-        }).max().unwrap() + 1;
-        println!("width: {:?}, height: {:?}", width, height);
-
-        let mut cells = vec![vec![Cell::new(); width]; height];
+        let mut cells = HashMap::new();
         for path in paths {
             for point in path.get_points() {
-                cells[point.1][point.0 - offset] = Cell::Rock;
+                cells.insert(point, Cell::Rock);
             }
         }
-        Cave { offset, cells, width, height }
+        let lowest_rock = cells.keys().map(|point| point.1).max().unwrap();
+        Cave {
+            cells,
+            lowest_rock,
+            floor: None,
+            rules: Rules::default(),
+            sources: vec![Source { point: SOURCE, weight: 1 }],
+            emission: Emission::RoundRobin,
+            credits: vec![0],
+            next_source: 0,
+            last_source: 0,
+            paths: vec![vec![]],
+        }
     }
 }
 
-struct MyError;
-
 impl Cave {
-    fn add_sand(&mut self) -> Result<(), MyError> {
-        let mut cell = (0, 500 - self.offset as i32);
+    fn with_floor(mut self, distance: usize) -> Self {
+        self.floor = Some(self.lowest_rock + distance);
+        self
+    }
+
+    fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    fn with_sources(mut self, sources: Vec<Source>, emission: Emission) -> Self {
+        self.credits = vec![0; sources.len()];
+        self.paths = vec![vec![]; sources.len()];
+        self.sources = sources;
+        self.emission = emission;
+        self.next_source = 0;
+        self.last_source = 0;
+        self
+    }
+
+    // The floor is infinite and nothing gets under it.
+    fn get(&self, point: (usize, usize)) -> Cell {
+        if self.floor.is_some_and(|floor| point.1 >= floor) {
+            Cell::Rock
+        } else {
+            *self.cells.get(&point).unwrap_or(&Cell::Empty)
+        }
+    }
+
+    fn current_path(&self) -> &[(usize, usize)] {
+        &self.paths[self.last_source]
+    }
+
+    // Sources in the order they should try to emit the next grain.
+    fn emission_order(&mut self) -> Vec<usize> {
+        let mut order = (0..self.sources.len()).map(|index| (self.next_source + index) % self.sources.len()).collect::<Vec<usize>>();
+        if self.emission == Emission::Weighted {
+            for (credit, source) in self.credits.iter_mut().zip(&self.sources) {
+                *credit += source.weight as isize;
+            }
+            order.sort_by_key(|index| -self.credits[*index]);
+        }
+        order
+    }
+
+    fn emitted(&mut self, index: usize) {
+        self.last_source = index;
+        self.next_source = (index + 1) % self.sources.len();
+        if self.emission == Emission::Weighted {
+            self.credits[index] -= self.sources.iter().map(|source| source.weight as isize).sum::<isize>();
+        }
+    }
+
+    fn add_sand(&mut self) -> SandOutcome {
+        for index in self.emission_order() {
+            if let Some(outcome) = self.drop_from(index) {
+                self.emitted(index);
+                return outcome;
+            }
+        }
+        SandOutcome::Blocked
+    }
+
+    // Moves back up are only allowed to cells the grain has not visited yet, so it cannot loop.
+    fn next_position(&self, path: &[(usize, usize)]) -> Option<(usize, usize)> {
+        let point = *path.last().unwrap();
+        self.rules
+            .moves
+            .iter()
+            .filter_map(|(dx, dy)| Some((point.0.checked_add_signed(*dx)?, point.1.checked_add_signed(*dy)?)))
+            .filter(|candidate| self.get(*candidate) == Cell::Empty)
+            .find(|candidate| candidate.1 > point.1 || !path.contains(candidate))
+    }
+
+    // Returns `None` when the source is blocked.
+    fn drop_from(&mut self, index: usize) -> Option<SandOutcome> {
+        let mut path = std::mem::take(&mut self.paths[index]);
+        while let Some(point) = path.last() {
+            if self.get(*point) == Cell::Empty {
+                break;
+            }
+            path.pop();
+        }
+        if path.is_empty() {
+            let source = self.sources[index].point;
+            if self.get(source) != Cell::Empty {
+                return None;
+            }
+            path.push(source);
+        }
+        let outcome = self.fall(&mut path);
+        self.paths[index] = path;
+        Some(outcome)
+    }
+
+    // Moves the grain at the end of `path` until it rests or leaves the cave.
+    fn fall(&mut self, path: &mut Vec<(usize, usize)>) -> SandOutcome {
         loop {
-            //println!("Cell {:?}", cell);
-            if cell.0 + 1 == self.height {
-                return Err(MyError);
-            } else if self.cells[cell.0 + 1][cell.1 as usize] == Cell::Empty {
-                cell = (cell.0 + 1, cell.1);
-            } else {
-                if cell.1 == 0 {
-                    return Err(MyError);
-                } else if self.cells[cell.0 + 1][(cell.1 - 1) as usize] == Cell::Empty {
-                    cell = (cell.0 + 1, cell.1 - 1);
-                } else if cell.1 as usize == self.width - 1 {
-                    return Err(MyError);
-                } else if self.cells[cell.0 + 1][(cell.1 + 1) as usize] == Cell::Empty {
-                    cell = (cell.0 + 1, cell.1 + 1);
-                } else {
-                    self.cells[cell.0][cell.1 as usize] = Cell::Sand;
-                    return Ok(());
-                }
+            let point = *path.last().unwrap();
+            if self.floor.is_none() && point.1 >= self.lowest_rock {
+                return SandOutcome::Abyss;
+            }
+            match self.next_position(path) {
+                Some(candidate) if self.flows_along_floor(point, candidate) => return SandOutcome::Abyss,
+                Some(candidate) => path.push(candidate),
+                None => {
+                    path.pop();
+                    self.settle(point);
+                    return SandOutcome::Rest(point);
+                },
             }
         }
     }
-}
 
-#[derive(Debug)]
-struct BigCave {
-    offset: usize,
-    width: usize,
-    height: usize,
-    cells: Vec<Vec<Cell>>,
-}
+    // With rules that move sand sideways, a grain going away on the floor past every rock would
+    // slide forever: it is lost like in the abyss.
+    fn flows_along_floor(&self, point: (usize, usize), candidate: (usize, usize)) -> bool {
+        if candidate.1 != point.1 || self.floor != Some(candidate.1 + 1) {
+            return false;
+        }
+        let rocks = self.cells.iter().filter(|(_, cell)| **cell == Cell::Rock).map(|(point, _)| point.0);
+        let (left, right) = rocks.fold((usize::MAX, 0), |(left, right), x| (left.min(x), right.max(x)));
+        (candidate.0 < point.0 && candidate.0 < left) || (candidate.0 > point.0 && candidate.0 > right)
+    }
 
-impl From<&Vec<Path>> for BigCave {
-    fn from(paths: &Vec<Path>) -> Self {
-        let path_offset = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.0).min().unwrap()
-        }).min().unwrap();
-        
-        let path_width = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.0).max().unwrap()
-        }).max().unwrap() - path_offset + 1;
-    
-        let height = paths.iter().map(|path| {
-            path.points.iter().map(|point| point.1).max().unwrap()
-        }).max().unwrap() + 1 + 2;
-        let (width, offset) = if path_width > 2 * height + 1 {
-            (path_width, path_offset)
-        } else {
-            (2 * height + 1, 500 - height)
-        };
-        println!("width: {:?}, height: {:?}", width, height);
+    // A new grain may lie on the path of another source, which then resumes above it.
+    fn settle(&mut self, point: (usize, usize)) {
+        self.cells.insert(point, Cell::Sand);
+        for path in self.paths.iter_mut() {
+            if let Some(position) = path.iter().position(|visited| *visited == point) {
+                path.truncate(position);
+            }
+        }
+    }
 
-        let mut cells = vec![vec![Cell::new(); width]; height];
-        for path in paths {
-            for point in path.get_points() {
-                cells[point.1][point.0 - offset] = Cell::Rock;
+    // With a floor and the default rules, sand ends up in every cell reachable from a source by
+    // falling, so the count is a row by row sweep of the triangles below the sources, minus the
+    // rocks and their shadows.
+    fn floor_capacity(&self) -> Option<usize> {
+        let floor = self.floor?;
+        if self.rules != Rules::default() {
+            return None;
+        }
+        let top = self.sources.iter().map(|source| source.point.1).min().unwrap();
+        let mut row: HashSet<usize> = HashSet::new();
+        let mut count = 0;
+        for y in top..floor {
+            row = row
+                .iter()
+                .flat_map(|x| [x.checked_sub(1), Some(*x), Some(x + 1)])
+                .flatten()
+                .chain(self.sources.iter().filter(|source| source.point.1 == y).map(|source| source.point.0))
+                .filter(|x| self.get((*x, y)) != Cell::Rock)
+                .collect();
+            count += row.len();
+        }
+        Some(count)
+    }
+
+    // Rock replaces any sand in its way; those grains are counted as displaced.
+    fn add_rock(&mut self, path: &Path) -> Edit {
+        let mut displaced = 0;
+        for point in path.get_points() {
+            if self.cells.insert(point, Cell::Rock) == Some(Cell::Sand) {
+                displaced += 1;
             }
         }
-        for i in 0..width {
-            cells[height - 1][i] = Cell::Rock;
+        self.after_edit(displaced)
+    }
+
+    fn remove_rock(&mut self, path: &Path) -> Edit {
+        for point in path.get_points() {
+            if self.cells.get(&point) == Some(&Cell::Rock) {
+                self.cells.remove(&point);
+            }
         }
-        BigCave { offset, cells, width, height }
+        self.after_edit(0)
+    }
+
+    fn after_edit(&mut self, displaced: usize) -> Edit {
+        self.lowest_rock = self.cells.iter().filter(|(_, cell)| **cell == Cell::Rock).map(|(point, _)| point.1).max().unwrap_or(0);
+        for path in self.paths.iter_mut() {
+            path.clear();
+        }
+        let (moved, lost) = self.resettle();
+        Edit { displaced, moved, lost }
     }
-}
 
-impl BigCave {
-    fn add_sand(&mut self) -> Result<(), MyError> {
-        let mut cell = (0, 500 - self.offset as i32);
+    // A grain is unsupported when one of the downward moves of the rules is open.
+    fn is_unsupported(&self, point: (usize, usize)) -> bool {
+        self.rules
+            .moves
+            .iter()
+            .filter(|(_, dy)| *dy > 0)
+            .filter_map(|(dx, dy)| Some((point.0.checked_add_signed(*dx)?, point.1.checked_add_signed(*dy)?)))
+            .any(|candidate| self.get(candidate) == Cell::Empty)
+    }
+
+    // Lets unsupported grains fall again, lowest first, until everything is at rest.
+    // Returns how many grains ended somewhere else and how many of those fell into the abyss.
+    // Grains look all the same, so a grain taking the place of another one counts as not moved.
+    fn resettle(&mut self) -> (usize, usize) {
+        let before = self.cells.iter().filter(|(_, cell)| **cell == Cell::Sand).map(|(point, _)| *point).collect::<Vec<(usize, usize)>>();
+        let mut lost = 0;
         loop {
-            //println!("Cell {:?}", cell);
-            if self.cells[cell.0][cell.1 as usize] == Cell::Sand {
-                return Err(MyError);
-            } else if cell.0 + 1 == self.height {
-                return Err(MyError);
-            } else if self.cells[cell.0 + 1][cell.1 as usize] == Cell::Empty {
-                cell = (cell.0 + 1, cell.1);
-            } else {
-                if cell.1 == 0 {
-                    return Err(MyError);
-                } else if self.cells[cell.0 + 1][(cell.1 - 1) as usize] == Cell::Empty {
-                    cell = (cell.0 + 1, cell.1 - 1);
-                } else if cell.1 as usize == self.width - 1 {
-                    return Err(MyError);
-                } else if self.cells[cell.0 + 1][(cell.1 + 1) as usize] == Cell::Empty {
-                    cell = (cell.0 + 1, cell.1 + 1);
-                } else {
-                    self.cells[cell.0][cell.1 as usize] = Cell::Sand;
-                    return Ok(());
+            let mut unsupported = self
+                .cells
+                .iter()
+                .filter(|(point, cell)| **cell == Cell::Sand && self.is_unsupported(**point))
+                .map(|(point, _)| *point)
+                .collect::<Vec<(usize, usize)>>();
+            if unsupported.is_empty() {
+                let moved = before.iter().filter(|point| self.get(**point) != Cell::Sand).count();
+                return (moved, lost);
+            }
+            unsupported.sort_by_key(|point| Reverse(point.1));
+            for point in unsupported {
+                if self.get(point) != Cell::Sand || !self.is_unsupported(point) {
+                    continue;
+                }
+                self.cells.remove(&point);
+                if self.fall(&mut vec![point]) == SandOutcome::Abyss {
+                    lost += 1;
                 }
             }
         }
     }
+
+    fn fill(&mut self) -> usize {
+        let mut step = 0;
+        while let SandOutcome::Rest(_) = self.add_sand() {
+            step += 1;
+        }
+        step
+    }
 }
 
 fn main() {
@@ -197,21 +422,250 @@ fn main() {
     // for path in &paths {
     //     println!("{:?}", path.get_points());
     // }
-    
+
+    match (env::args().nth(1).as_deref(), env::args().nth(2)) {
+        (Some("render"), _) => {
+            let mut cave = Cave::from(&paths);
+            cave.fill();
+            let outcome = cave.add_sand();
+            let window = render::Window::around(&cave);
+            println!("{}", render::render(&cave, &window, &render::trail(&cave, &outcome)));
+            return;
+        },
+        (Some("animate"), floor) => {
+            let mut cave = Cave::from(&paths);
+            if floor.is_some() {
+                cave = cave.with_floor(2);
+            }
+            let window = render::Window::around(&cave);
+            render::animate(&mut cave, &window, Duration::from_millis(30));
+            return;
+        },
+        (Some("frames"), Some(directory)) => {
+            let mut cave = Cave::from(&paths).with_floor(2);
+            let window = render::Window::around(&cave);
+            let frames = render::export_frames(&mut cave, &window, 100, 4, &directory).expect("oops");
+            println!("{} frames written to {}", frames, directory);
+            return;
+        },
+        (Some("report"), format) => {
+            let mut cave = Cave::from(&paths);
+            if env::args().any(|arg| arg == "floor") {
+                cave = cave.with_floor(2);
+            }
+            let report = report::fill_with_report(&mut cave, &paths);
+            if format.as_deref() == Some("json") {
+                println!("{}", report.to_json());
+            } else {
+                println!("{}", report.to_table());
+            }
+            return;
+        },
+        (Some("edit"), _) => {
+            let mut cave = Cave::from(&paths);
+            if env::args().any(|arg| arg == "floor") {
+                cave = cave.with_floor(2);
+            }
+            let stdin = io::stdin();
+            repl::run(&mut cave, stdin.lock(), io::stdout()).expect("oops");
+            return;
+        },
+        (Some("variant"), Some(rules)) => {
+            let rules = match rules.as_str() {
+                "sliding" => Rules::sliding(2),
+                "water" => Rules::water(),
+                table => Rules::from(table),
+            };
+            let sources = env::args().skip(3).map(|source| Source::from(source.as_str())).collect::<Vec<Source>>();
+            let mut cave = Cave::from(&paths).with_rules(rules);
+            if !sources.is_empty() {
+                cave = cave.with_sources(sources, Emission::Weighted);
+            }
+            println!("{} units of sand", cave.fill());
+            return;
+        },
+        _ => {},
+    }
+
     let mut cave = Cave::from(&paths);
-    //println!("{:?}", cave);
+    println!("{} units of sand", cave.fill());
+
+    let big_cave = Cave::from(&paths).with_floor(2);
+    println!("{} units of sand for the big cave", big_cave.floor_capacity().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cave, Cell, Edit, Emission, Path, Rules, SandOutcome, Source};
+
+    pub fn example() -> Vec<Path> {
+        vec![
+            Path::from("498,4 -> 498,6 -> 496,6".to_string()),
+            Path::from("503,4 -> 502,4 -> 502,9 -> 494,9".to_string()),
+        ]
+    }
+
+    #[test]
+    fn first_grain_rests_on_rock() {
+        let mut cave = Cave::from(&example());
 
-    let mut step = 0;
-    while cave.add_sand().is_ok() {
-        step += 1;
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((500, 8)));
     }
-    println!("{} units of sand", step);
-    
-    let mut big_cave = BigCave::from(&paths);
-    
-    let mut step = 0;
-    while big_cave.add_sand().is_ok() {
-        step += 1;
+
+    #[test]
+    fn fill_until_abyss() {
+        let mut cave = Cave::from(&example());
+
+        assert_eq!(cave.fill(), 24);
+        assert_eq!(cave.add_sand(), SandOutcome::Abyss);
+    }
+
+    #[test]
+    fn fill_until_source_blocked() {
+        let mut cave = Cave::from(&example()).with_floor(2);
+
+        assert_eq!(cave.fill(), 93);
+        assert_eq!(cave.add_sand(), SandOutcome::Blocked);
+    }
+
+    #[test]
+    fn sources_emit_round_robin() {
+        let sources = vec![Source { point: (497, 0), weight: 1 }, Source { point: (500, 0), weight: 1 }];
+        let mut cave = Cave::from(&example()).with_sources(sources, Emission::RoundRobin);
+
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((497, 5)));
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((500, 8)));
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((495, 8)));
+    }
+
+    #[test]
+    fn sources_emit_by_weight() {
+        let sources = vec![Source { point: (500, 0), weight: 2 }, Source { point: (600, 0), weight: 1 }];
+        let mut cave = Cave::from(&example()).with_floor(2).with_sources(sources, Emission::Weighted);
+        let order = (0..6)
+            .map(|_| {
+                cave.add_sand();
+                cave.last_source
+            })
+            .collect::<Vec<usize>>();
+
+        assert_eq!(order, vec![0, 1, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn blocked_source_is_skipped() {
+        let sources = vec![Source { point: (500, 10), weight: 1 }, Source { point: (600, 10), weight: 1 }];
+        let mut cave = Cave::from(&example()).with_floor(2).with_sources(sources, Emission::RoundRobin);
+
+        assert_eq!(cave.fill(), 2);
+        assert_eq!(cave.add_sand(), SandOutcome::Blocked);
+    }
+
+    #[test]
+    fn parse_rules() {
+        assert_eq!(Rules::from("0,1 -1,1 1,1"), Rules::default());
+        assert_eq!(Rules::sliding(2), Rules::from("0,1 -1,1 1,1 -2,1 2,1"));
+    }
+
+    #[test]
+    fn sliding_sand_escapes_through_gap() {
+        let mut cave = Cave::from(&example()).with_rules(Rules::sliding(2));
+
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((500, 8)));
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((499, 8)));
+        assert_eq!(cave.add_sand(), SandOutcome::Abyss);
+    }
+
+    #[test]
+    fn water_spreads_sideways() {
+        let bucket = vec![Path::from("495,2 -> 495,5 -> 505,5 -> 505,2".to_string())];
+        let mut cave = Cave::from(&bucket).with_rules(Rules::water());
+
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((496, 4)));
+        assert_eq!(cave.add_sand(), SandOutcome::Rest((497, 4)));
+    }
+
+    #[test]
+    fn parse_source() {
+        assert_eq!(Source::from("500,0"), Source { point: (500, 0), weight: 1 });
+        assert_eq!(Source::from("520,3*4"), Source { point: (520, 3), weight: 4 });
+    }
+
+    #[test]
+    fn add_rock_under_sand() {
+        let mut cave = Cave::from(&example());
+        cave.fill();
+        let edit = cave.add_rock(&Path::from("499,8 -> 501,8".to_string()));
+
+        assert_eq!(edit, Edit { displaced: 3, moved: 0, lost: 0 });
+        assert_eq!(cave.cells.values().filter(|cell| **cell == Cell::Sand).count(), 21);
+        assert_eq!(cave.fill(), 0);
+    }
+
+    #[test]
+    fn remove_rock_resettles_sand() {
+        let mut cave = Cave::from(&example()).with_floor(2);
+        cave.fill();
+        let edit = cave.remove_rock(&Path::from("496,6 -> 498,6".to_string()));
+
+        assert_eq!(edit, Edit { displaced: 0, moved: 4, lost: 0 });
+        assert!(cave.cells.keys().all(|point| cave.get(*point) != Cell::Sand || !cave.is_unsupported(*point)));
+
+        cave.fill();
+
+        assert_eq!(Some(cave.cells.values().filter(|cell| **cell == Cell::Sand).count()), cave.floor_capacity());
+    }
+
+    #[test]
+    fn remove_rock_loses_sand_into_abyss() {
+        let mut cave = Cave::from(&example());
+        cave.fill();
+        let edit = cave.remove_rock(&Path::from("494,9 -> 502,9".to_string()));
+
+        assert_eq!(edit, Edit { displaced: 0, moved: 23, lost: 23 });
+        assert_eq!(cave.cells.get(&(497, 5)), Some(&Cell::Sand));
+    }
+
+    #[test]
+    fn floor_capacity_matches_simulation() {
+        let cave = Cave::from(&example()).with_floor(2);
+
+        assert_eq!(cave.floor_capacity(), Some(93));
+        assert_eq!(Cave::from(&example()).floor_capacity(), None);
+
+        let sources = vec![Source { point: (500, 0), weight: 1 }, Source { point: (505, 3), weight: 1 }];
+        let mut cave = Cave::from(&example()).with_floor(2).with_sources(sources, Emission::RoundRobin);
+        let capacity = cave.floor_capacity();
+
+        assert_eq!(capacity, Some(cave.fill()));
+    }
+
+    #[test]
+    fn remove_rock_under_a_pile_on_the_floor() {
+        let mut cave = Cave::from(&example()).with_floor(2);
+        cave.fill();
+        let sand = cave.cells.values().filter(|cell| **cell == Cell::Sand).count();
+        let edit = cave.remove_rock(&Path::from("494,9 -> 502,9".to_string()));
+
+        assert_eq!(edit, Edit { displaced: 0, moved: 16, lost: 0 });
+        assert_eq!(cave.cells.values().filter(|cell| **cell == Cell::Sand).count(), sand);
+    }
+
+    #[test]
+    fn source_below_the_floor_is_blocked() {
+        let sources = vec![Source { point: (520, 14), weight: 1 }];
+        let mut cave = Cave::from(&example()).with_floor(2).with_sources(sources, Emission::RoundRobin);
+
+        assert_eq!(cave.fill(), 0);
+        assert_eq!(cave.add_sand(), SandOutcome::Blocked);
+    }
+
+    #[test]
+    fn water_flows_away_along_the_floor() {
+        let mut cave = Cave::from(&example()).with_floor(2).with_rules(Rules::water());
+
+        assert_eq!(cave.add_sand(), SandOutcome::Abyss);
+        assert_eq!(cave.current_path().last(), Some(&(493, 10)));
+        assert_eq!(cave.fill(), 0);
     }
-    println!("{} units of sand for the big cave", step);
 }
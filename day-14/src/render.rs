@@ -0,0 +1,202 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{Cave, Cell, SandOutcome};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Window {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+impl Window {
+    // Everything sand can reach: rocks and sand with a one cell margin, or the whole triangles
+    // below the sources when there is a floor.
+    pub fn around(cave: &Cave) -> Self {
+        let (x, y) = cave.sources[0].point;
+        let mut window = Window { left: x, top: y, right: x, bottom: y };
+        for source in &cave.sources {
+            window.left = window.left.min(source.point.0);
+            window.top = window.top.min(source.point.1);
+            window.right = window.right.max(source.point.0);
+            window.bottom = window.bottom.max(source.point.1);
+        }
+        for (x, y) in cave.cells.keys() {
+            window.left = window.left.min(*x);
+            window.right = window.right.max(*x);
+            window.bottom = window.bottom.max(*y);
+        }
+        window.left = window.left.saturating_sub(1);
+        window.right += 1;
+        if let Some(floor) = cave.floor {
+            // A source on or below the floor cannot spread any sand.
+            for source in &cave.sources {
+                let spread = floor.saturating_sub(source.point.1);
+                window.left = window.left.min(source.point.0.saturating_sub(spread));
+                window.right = window.right.max(source.point.0 + spread);
+            }
+            window.bottom = window.bottom.max(floor);
+        }
+        window
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Pixel {
+    Air,
+    Rock,
+    Sand,
+    Source,
+    Trail,
+}
+
+impl Pixel {
+    fn to_char(self) -> char {
+        match self {
+            Pixel::Air => '.',
+            Pixel::Rock => '#',
+            Pixel::Sand => 'o',
+            Pixel::Source => '+',
+            Pixel::Trail => '~',
+        }
+    }
+
+    fn to_rgb(self) -> [u8; 3] {
+        match self {
+            Pixel::Air => [20, 20, 30],
+            Pixel::Rock => [110, 110, 110],
+            Pixel::Sand => [230, 190, 90],
+            Pixel::Source => [220, 40, 40],
+            Pixel::Trail => [80, 140, 230],
+        }
+    }
+}
+
+// `trail` is drawn with `~`, typically the path of a grain falling into the abyss.
+fn pixels(cave: &Cave, window: &Window, trail: &[(usize, usize)]) -> Vec<Vec<Pixel>> {
+    (window.top..=window.bottom)
+        .map(|y| {
+            (window.left..=window.right)
+                .map(|x| match cave.get((x, y)) {
+                    Cell::Rock => Pixel::Rock,
+                    Cell::Sand => Pixel::Sand,
+                    Cell::Empty if cave.sources.iter().any(|source| source.point == (x, y)) => Pixel::Source,
+                    Cell::Empty if trail.contains(&(x, y)) => Pixel::Trail,
+                    Cell::Empty => Pixel::Air,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn render(cave: &Cave, window: &Window, trail: &[(usize, usize)]) -> String {
+    pixels(cave, window, trail)
+        .iter()
+        .map(|line| line.iter().map(|pixel| pixel.to_char()).collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn write_ppm(cave: &Cave, window: &Window, trail: &[(usize, usize)], scale: usize, file_name: &str) -> io::Result<()> {
+    let pixels = pixels(cave, window, trail);
+    let mut writer = BufWriter::new(File::create(file_name)?);
+    write!(writer, "P6\n{} {}\n255\n", pixels[0].len() * scale, pixels.len() * scale)?;
+    for line in &pixels {
+        for _ in 0..scale {
+            for pixel in line {
+                for _ in 0..scale {
+                    writer.write_all(&pixel.to_rgb())?;
+                }
+            }
+        }
+    }
+    writer.flush()
+}
+
+// The trail to draw after a grain: its fall path when it went into the abyss.
+pub fn trail(cave: &Cave, outcome: &SandOutcome) -> Vec<(usize, usize)> {
+    match outcome {
+        SandOutcome::Abyss => cave.current_path().to_vec(),
+        _ => vec![],
+    }
+}
+
+pub fn animate(cave: &mut Cave, window: &Window, delay: Duration) {
+    loop {
+        let outcome = cave.add_sand();
+        println!("\x1b[2J\x1b[H{}", render(cave, window, &trail(cave, &outcome)));
+        io::stdout().flush().unwrap();
+        if !matches!(outcome, SandOutcome::Rest(_)) {
+            break;
+        }
+        thread::sleep(delay);
+    }
+}
+
+// Writes one image every `every` grains, plus the final state. Returns the number of frames.
+pub fn export_frames(cave: &mut Cave, window: &Window, every: usize, scale: usize, directory: &str) -> io::Result<usize> {
+    fs::create_dir_all(directory)?;
+    let mut frames = 0;
+    let mut grains = 0;
+    loop {
+        let outcome = cave.add_sand();
+        let finished = !matches!(outcome, SandOutcome::Rest(_));
+        if finished || grains % every == 0 {
+            write_ppm(cave, window, &trail(cave, &outcome), scale, &format!("{}/frame-{:05}.ppm", directory, frames))?;
+            frames += 1;
+        }
+        if finished {
+            return Ok(frames);
+        }
+        grains += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        render::{render, trail, Window},
+        tests::example,
+        Cave, Emission, Source,
+    };
+
+    #[test]
+    fn render_example_after_abyss() {
+        let mut cave = Cave::from(&example());
+        cave.fill();
+        let outcome = cave.add_sand();
+        let window = Window::around(&cave);
+
+        assert_eq!(window, Window { left: 493, top: 0, right: 504, bottom: 9 });
+        assert_eq!(
+            render(&cave, &window, &trail(&cave, &outcome)),
+            [
+                ".......+....",
+                ".......~....",
+                "......~o....",
+                ".....~ooo...",
+                "....~#ooo##.",
+                "...~o#ooo#..",
+                "..~###ooo#..",
+                "..~..oooo#..",
+                ".~o.ooooo#..",
+                "~#########..",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn window_with_a_source_below_the_floor() {
+        let sources = vec![Source { point: (500, 0), weight: 1 }, Source { point: (520, 14), weight: 1 }];
+        let cave = Cave::from(&example()).with_floor(2).with_sources(sources, Emission::RoundRobin);
+
+        assert_eq!(Window::around(&cave), Window { left: 489, top: 0, right: 521, bottom: 14 });
+    }
+}
@@ -0,0 +1,100 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    render::{render, trail, Window},
+    Cave, Edit, Path, SandOutcome,
+};
+
+// Same syntax as the input, checked beforehand since `Path::from` expects well-formed lines.
+fn parse_path(input: &str) -> Option<Path> {
+    let points = input
+        .split(" -> ")
+        .map(|pair| {
+            let (x, y) = pair.trim().split_once(',')?;
+            Some((x.parse::<usize>().ok()?, y.parse::<usize>().ok()?))
+        })
+        .collect::<Option<Vec<(usize, usize)>>>()?;
+    if points.windows(2).all(|pair| pair[0].0 == pair[1].0 || pair[0].1 == pair[1].1) {
+        Some(Path { points })
+    } else {
+        None
+    }
+}
+
+fn describe(edit: &Edit) -> String {
+    format!("{} displaced, {} moved, {} lost", edit.displaced, edit.moved, edit.lost)
+}
+
+// Commands: `rock <path>`, `unrock <path>`, `drop [n]`, `show`, `quit`.
+pub fn run(cave: &mut Cave, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut last = None;
+    for line in input.lines() {
+        let line = line?;
+        let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match (command, parse_path(argument)) {
+            ("rock", Some(path)) => writeln!(output, "{}", describe(&cave.add_rock(&path)))?,
+            ("unrock", Some(path)) => writeln!(output, "{}", describe(&cave.remove_rock(&path)))?,
+            ("drop", _) => {
+                let grains = argument.trim().parse::<usize>().unwrap_or(1);
+                let mut rested = 0;
+                for _ in 0..grains {
+                    let outcome = cave.add_sand();
+                    let at_rest = matches!(outcome, SandOutcome::Rest(_));
+                    last = Some(outcome);
+                    if !at_rest {
+                        break;
+                    }
+                    rested += 1;
+                }
+                match last {
+                    Some(SandOutcome::Abyss) => writeln!(output, "{} grains at rest, then one fell into the abyss", rested)?,
+                    Some(SandOutcome::Blocked) => writeln!(output, "{} grains at rest, then the source was blocked", rested)?,
+                    _ => writeln!(output, "{} grains at rest", rested)?,
+                }
+            },
+            ("show", _) => {
+                let trail = last.as_ref().map_or(vec![], |outcome| trail(cave, outcome));
+                writeln!(output, "{}", render(cave, &Window::around(cave), &trail))?;
+            },
+            ("quit", _) => break,
+            ("", _) => {},
+            _ => writeln!(output, "Unknown command: {}", line)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{repl::run, tests::example, Cave};
+
+    #[test]
+    fn block_the_source_then_drop() {
+        let mut cave = Cave::from(&example());
+        let mut output = vec![];
+        run(&mut cave, "drop 30\nrock 493,3 -> 507,3\ndrop 2\nrock 499,1 -> 501,1\nshow\nbad".as_bytes(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            [
+                "24 grains at rest, then one fell into the abyss",
+                "3 displaced, 0 moved, 0 lost",
+                "2 grains at rest",
+                "0 displaced, 0 moved, 0 lost",
+                "........+........",
+                ".......###.......",
+                ".......ooo.......",
+                ".###############.",
+                "......#ooo##.....",
+                ".....o#ooo#......",
+                "....###ooo#......",
+                "......oooo#......",
+                "...o.ooooo#......",
+                "..#########......",
+                "Unknown command: bad",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{Cave, Cell, Path, SandOutcome};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Column {
+    pub sand: usize,
+    pub top: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub grains: usize,
+    pub end: SandOutcome,
+    // Row -> number of grains at rest once the first grain came to rest in that row.
+    pub first_rest_by_row: BTreeMap<usize, usize>,
+    pub columns: BTreeMap<usize, Column>,
+    // Rock cells next to at least one grain, the floor excluded.
+    pub rock_touched: usize,
+    // Per input path, the grain after which sand lies on top of all its exposed cells.
+    pub structures_covered: Vec<Option<usize>>,
+}
+
+// Cells right above the rocks of a path that are not rocks themselves.
+fn exposed_tops(cave: &Cave, path: &Path) -> HashSet<(usize, usize)> {
+    path.get_points()
+        .iter()
+        .filter(|(_, y)| *y > 0)
+        .map(|(x, y)| (*x, y - 1))
+        .filter(|above| cave.get(*above) != Cell::Rock)
+        .collect()
+}
+
+pub fn fill_with_report(cave: &mut Cave, paths: &[Path]) -> Report {
+    let mut watchers: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    let mut remaining = vec![];
+    for (index, path) in paths.iter().enumerate() {
+        let tops = exposed_tops(cave, path);
+        remaining.push(tops.iter().filter(|top| cave.get(**top) != Cell::Sand).count());
+        for top in tops {
+            watchers.entry(top).or_default().push(index);
+        }
+    }
+    let mut structures_covered = remaining.iter().map(|left| if *left == 0 { Some(0) } else { None }).collect::<Vec<Option<usize>>>();
+
+    let mut grains = 0;
+    let mut first_rest_by_row = BTreeMap::new();
+    let end = loop {
+        match cave.add_sand() {
+            SandOutcome::Rest(point) => {
+                grains += 1;
+                first_rest_by_row.entry(point.1).or_insert(grains);
+                for index in watchers.get(&point).unwrap_or(&vec![]) {
+                    remaining[*index] -= 1;
+                    if remaining[*index] == 0 {
+                        structures_covered[*index] = Some(grains);
+                    }
+                }
+            },
+            outcome => break outcome,
+        }
+    };
+
+    let mut columns: BTreeMap<usize, Column> = BTreeMap::new();
+    let mut rock_touched = HashSet::new();
+    for ((x, y), cell) in &cave.cells {
+        if *cell != Cell::Sand {
+            continue;
+        }
+        let column = columns.entry(*x).or_insert(Column { sand: 0, top: *y });
+        column.sand += 1;
+        column.top = column.top.min(*y);
+        let neighbors = [x.checked_sub(1).map(|left| (left, *y)), Some((x + 1, *y)), y.checked_sub(1).map(|up| (*x, up)), Some((*x, y + 1))];
+        for neighbor in neighbors.into_iter().flatten() {
+            if cave.cells.get(&neighbor) == Some(&Cell::Rock) {
+                rock_touched.insert(neighbor);
+            }
+        }
+    }
+
+    Report { grains, end, first_rest_by_row, columns, rock_touched: rock_touched.len(), structures_covered }
+}
+
+impl Report {
+    pub fn to_json(&self) -> String {
+        let end = match self.end {
+            SandOutcome::Abyss => "abyss",
+            SandOutcome::Blocked => "blocked",
+            SandOutcome::Rest(_) => unreachable!(),
+        };
+        let rows = self.first_rest_by_row.iter().map(|(row, grains)| format!("\"{}\":{}", row, grains)).collect::<Vec<String>>();
+        let columns = self
+            .columns
+            .iter()
+            .map(|(x, column)| format!("{{\"x\":{},\"sand\":{},\"top\":{}}}", x, column.sand, column.top))
+            .collect::<Vec<String>>();
+        let covered = self
+            .structures_covered
+            .iter()
+            .map(|grain| grain.map_or("null".to_string(), |grain| grain.to_string()))
+            .collect::<Vec<String>>();
+        format!(
+            "{{\"grains\":{},\"end\":\"{}\",\"first_rest_by_row\":{{{}}},\"columns\":[{}],\"rock_touched\":{},\"structures_covered\":[{}]}}",
+            self.grains,
+            end,
+            rows.join(","),
+            columns.join(","),
+            self.rock_touched,
+            covered.join(",")
+        )
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut lines = vec![
+            format!("grains at rest: {}", self.grains),
+            format!("stopped by:     {:?}", self.end),
+            format!("rock touched:   {}", self.rock_touched),
+            String::new(),
+            format!("{:>6} {:>8}", "row", "grains"),
+        ];
+        for (row, grains) in &self.first_rest_by_row {
+            lines.push(format!("{:>6} {:>8}", row, grains));
+        }
+        lines.push(String::new());
+        lines.push(format!("{:>6} {:>6} {:>6}", "column", "sand", "top"));
+        for (x, column) in &self.columns {
+            lines.push(format!("{:>6} {:>6} {:>6}", x, column.sand, column.top));
+        }
+        lines.push(String::new());
+        lines.push(format!("{:>6} {:>8}", "path", "covered"));
+        for (index, grain) in self.structures_covered.iter().enumerate() {
+            lines.push(format!("{:>6} {:>8}", index, grain.map_or("-".to_string(), |grain| grain.to_string())));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        report::{fill_with_report, Column},
+        tests::example,
+        Cave, SandOutcome,
+    };
+
+    #[test]
+    fn report_example_until_abyss() {
+        let paths = example();
+        let mut cave = Cave::from(&paths);
+        let report = fill_with_report(&mut cave, &paths);
+
+        assert_eq!(report.grains, 24);
+        assert_eq!(report.end, SandOutcome::Abyss);
+        assert_eq!(report.first_rest_by_row.get(&8), Some(&1));
+        assert_eq!(report.first_rest_by_row.get(&2), Some(&22));
+        assert_eq!(report.columns.get(&500), Some(&Column { sand: 7, top: 2 }));
+        assert_eq!(report.structures_covered, vec![None, None]);
+    }
+
+    #[test]
+    fn report_example_with_floor() {
+        let paths = example();
+        let mut cave = Cave::from(&paths).with_floor(2);
+        let report = fill_with_report(&mut cave, &paths);
+
+        assert_eq!(report.grains, 93);
+        assert_eq!(report.end, SandOutcome::Blocked);
+        assert_eq!(report.first_rest_by_row.get(&0), Some(&93));
+        assert!(report.structures_covered.iter().all(|grain| grain.is_some()));
+        assert!(report.to_json().starts_with("{\"grains\":93,\"end\":\"blocked\",\"first_rest_by_row\":{\"0\":93,"));
+    }
+}
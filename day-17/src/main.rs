@@ -1,6 +1,9 @@
-use std::{collections::VecDeque, fs};
+use std::{collections::VecDeque, env, fs};
 
-#[derive(Debug, PartialEq)]
+mod replay;
+mod solver;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum Move {
     Left,
     Right,
@@ -22,7 +25,7 @@ enum Cell {
     Full,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 struct Rock {
     cells: Vec<(usize, usize)>,
 }
@@ -62,50 +65,156 @@ impl Rock {
     }
 }
 
+const DEFAULT_ROCKS: &str = "####
+
+.#.
+###
+.#.
+
+..#
+..#
+###
+
+#
+#
+#
+#
+
+##
+##";
+
+#[derive(Debug, PartialEq, Clone)]
+struct RockShape {
+    cells: Vec<(usize, usize)>,
+}
+
+impl From<&str> for RockShape {
+    fn from(input: &str) -> Self {
+        let lines = input.lines().map(|line| line.trim()).filter(|line| !line.is_empty()).collect::<Vec<&str>>();
+        let cells = lines
+            .iter()
+            .rev()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars().enumerate().filter(|(_, c)| *c == '#').map(move |(x, _)| (y, x))
+            })
+            .collect::<Vec<(usize, usize)>>();
+        RockShape { cells }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct ChamberConfig {
+    width: usize,
+    left_gap: usize,
+    bottom_gap: usize,
+    shapes: Vec<RockShape>,
+}
+
+impl Default for ChamberConfig {
+    fn default() -> Self {
+        ChamberConfig::parse(DEFAULT_ROCKS).unwrap()
+    }
+}
+
+impl ChamberConfig {
+    // Blocks are separated by empty lines: a block of `key: value` lines sets the chamber
+    // parameters, any other block is the ASCII art of a rock, as drawn in the puzzle. Every rock
+    // must fit in the chamber when launched.
+    fn parse(input: &str) -> Result<Self, String> {
+        let mut config = ChamberConfig { width: 7, left_gap: 2, bottom_gap: 3, shapes: vec![] };
+        for block in input.split("\n\n").filter(|block| !block.trim().is_empty()) {
+            if block.contains(':') {
+                for line in block.lines().filter(|line| !line.trim().is_empty()) {
+                    let (key, value) = line.split_once(':').ok_or_else(|| format!("expected `key: value`, found `{}`", line.trim()))?;
+                    let value = value.trim().parse::<usize>().map_err(|_| format!("`{}` is not a number in `{}`", value.trim(), line.trim()))?;
+                    match key.trim() {
+                        "width" => config.width = value,
+                        "left" => config.left_gap = value,
+                        "bottom" => config.bottom_gap = value,
+                        key => return Err(format!("unknown key `{}`, expected `width`, `left` or `bottom`", key)),
+                    }
+                }
+            } else {
+                config.shapes.push(RockShape::from(block));
+            }
+        }
+        if config.width == 0 {
+            return Err("the chamber cannot have a width of 0".to_string());
+        }
+        if config.shapes.is_empty() {
+            config.shapes = ChamberConfig::default().shapes;
+        }
+        for (index, shape) in config.shapes.iter().enumerate() {
+            if shape.cells.is_empty() {
+                return Err(format!("rock {} has no cells", index + 1));
+            }
+            let shape_width = shape.cells.iter().map(|(_, x)| x + 1).max().unwrap();
+            if config.left_gap + shape_width > config.width {
+                return Err(format!("rock {} is {} wide and does not fit {} from the left of a chamber {} wide", index + 1, shape_width, config.left_gap, config.width));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone)]
 struct RockLauncher {
     current: usize,
+    shapes: Vec<RockShape>,
+    left_gap: usize,
+    bottom_gap: usize,
 }
 
 impl RockLauncher {
-    fn new() -> Self {
-        RockLauncher { current: 0 }
+    fn new(config: &ChamberConfig) -> Self {
+        RockLauncher { current: 0, shapes: config.shapes.clone(), left_gap: config.left_gap, bottom_gap: config.bottom_gap }
     }
 
     fn launch(&mut self, height: usize) -> Rock {
-        let starting_height = height + 4;
-        let rock = match self.current % 5 {
-            0 => Rock { cells: vec![(starting_height, 3), (starting_height, 4), (starting_height, 5), (starting_height, 6)] },
-            1 => Rock { cells: vec![(starting_height, 4), (starting_height + 1, 3), (starting_height + 1, 4), (starting_height + 1, 5), (starting_height + 2, 4)] },
-            2 => Rock { cells: vec![(starting_height, 3), (starting_height, 4), (starting_height, 5), (starting_height + 1, 5), (starting_height + 2, 5)] },
-            3 => Rock { cells: vec![(starting_height, 3), (starting_height + 1, 3), (starting_height + 2, 3), (starting_height + 3, 3)] },
-            4 => Rock { cells: vec![(starting_height, 3), (starting_height, 4), (starting_height + 1, 3), (starting_height + 1, 4)] },
-            _ => unreachable!(),
-        };
+        let starting_height = height + self.bottom_gap + 1;
+        let starting_column = self.left_gap + 1;
+        let shape = &self.shapes[self.current % self.shapes.len()];
+        let rock = Rock { cells: shape.cells.iter().map(|(y, x)| (starting_height + y, starting_column + x)).collect() };
         self.current += 1;
         rock
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 struct Grid {
-    cells: VecDeque<[Cell; 9]>,
-    lowest: [usize; 9],
+    cells: VecDeque<Vec<Cell>>,
+    lowest: Vec<usize>,
     offset: usize,
+    width: usize,
 }
 
 impl Grid {
-    fn new() -> Self {
-        Grid { cells: VecDeque::from([[Cell::Full; 9]]), lowest: [usize::MAX, 0, 0, 0, 0, 0, 0, 0, usize::MAX], offset: 0 }
+    fn new(config: &ChamberConfig) -> Self {
+        let mut lowest = vec![0; config.width + 2];
+        lowest[0] = usize::MAX;
+        lowest[config.width + 1] = usize::MAX;
+        Grid {
+            cells: VecDeque::from([vec![Cell::Full; config.width + 2]]),
+            lowest,
+            offset: 0,
+            width: config.width,
+        }
     }
-    
+
     fn add_line(&mut self) {
-        self.cells.push_back([Cell::Full, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Empty, Cell::Full]);
+        let mut line = vec![Cell::Empty; self.width + 2];
+        line[0] = Cell::Full;
+        line[self.width + 1] = Cell::Full;
+        self.cells.push_back(line);
     }
 
-    fn add_three_lines(&mut self) {
-        self.add_line();
-        self.add_line();
-        self.add_line();
+    // Makes room above the tower for the next rock, whatever its height.
+    fn add_lines_for(&mut self, rock: &Rock) {
+        let top = rock.cells.iter().map(|(y, _)| *y).max().unwrap();
+        while self.offset + self.cells.len() <= top {
+            self.add_line();
+        }
     }
 
     fn add_rock(&mut self, rock: &Rock) {
@@ -134,8 +243,80 @@ impl Grid {
         .any(|(y, x)| self.cells[y - self.offset][*x] == Cell::Full)
     }
 
+    fn cell(&self, y: usize, x: usize) -> Cell {
+        if y < self.offset || y - self.offset >= self.cells.len() {
+            Cell::Empty
+        } else {
+            self.cells[y - self.offset][x]
+        }
+    }
+
+    fn push(&self, rock: &mut Rock, jet: &Move) {
+        match jet {
+            Move::Left => {
+                if !self.intersect(rock.left()) {
+                    rock.go_left();
+                }
+            },
+            Move::Right => {
+                if !self.intersect(rock.right()) {
+                    rock.go_right();
+                }
+            }
+        }
+    }
+
+    // Returns whether the rock could go down; otherwise it comes to rest in the grid.
+    fn fall(&mut self, rock: &mut Rock) -> bool {
+        let can_go_down = !self.intersect(rock.bottom());
+        if can_go_down {
+            rock.go_down();
+        } else {
+            self.add_rock(rock);
+        }
+        can_go_down
+    }
+
     fn max_height(&self) -> usize {
-        self.lowest[1..8].iter().max().unwrap().clone()
+        *self.lowest[1..=self.width].iter().max().unwrap()
+    }
+}
+
+// A chamber with a falling rock, driven one jet at a time by the caller.
+#[derive(Debug, Clone)]
+struct Environment {
+    grid: Grid,
+    launcher: RockLauncher,
+    rock: Rock,
+    rocks_settled: usize,
+}
+
+impl Environment {
+    fn new(config: &ChamberConfig) -> Self {
+        let mut grid = Grid::new(config);
+        let mut launcher = RockLauncher::new(config);
+        let rock = launcher.launch(grid.max_height());
+        grid.add_lines_for(&rock);
+        Environment { grid, launcher, rock, rocks_settled: 0 }
+    }
+
+    // Applies one jet then lets the rock fall one unit. Returns whether the rock came to rest.
+    fn step(&mut self, jet: &Move) -> bool {
+        self.grid.push(&mut self.rock, jet);
+        if self.grid.fall(&mut self.rock) {
+            false
+        } else {
+            self.rock = self.launcher.launch(self.grid.max_height());
+            self.grid.add_lines_for(&self.rock);
+            self.rocks_settled += 1;
+            true
+        }
+    }
+
+    // Depth of each column below the top of the tower.
+    fn surface(&self) -> Vec<usize> {
+        let height = self.grid.max_height();
+        self.grid.lowest[1..=self.grid.width].iter().map(|lowest| height - lowest).collect()
     }
 }
 
@@ -143,12 +324,41 @@ fn main() {
     let file_name = "input/day-17-test";
     let content = fs::read_to_string(file_name).expect("oops");
 
-    let moves = content.chars().map(|char| Move::from(char)).collect::<Vec<Move>>();
+    let config = match fs::read_to_string("input/day-17-rocks") {
+        Ok(rocks) => match ChamberConfig::parse(&rocks) {
+            Ok(config) => config,
+            Err(error) => {
+                println!("input/day-17-rocks: {}", error);
+                return;
+            },
+        },
+        Err(_) => ChamberConfig::default(),
+    };
+
+    let moves = content.trim().chars().map(Move::from).collect::<Vec<Move>>();
     // println!("Moves: {:?}", moves);
-    let mut move_iter = moves.iter().cycle();
+    let move_iter = moves.iter().cycle();
 
-    let mut grid = Grid::new();
-    let mut rock_launcher = RockLauncher::new();
+    match (env::args().nth(1).as_deref(), env::args().nth(2).map(|n| n.parse::<usize>().unwrap())) {
+        (Some("replay"), _) => {
+            replay::run(&config, &moves);
+            return;
+        },
+        (Some("solve"), Some(rocks)) => {
+            let objective = match env::args().nth(3) {
+                Some(surface) => solver::Objective::Surface(surface.split(',').map(|depth| depth.parse::<usize>().unwrap()).collect()),
+                None => solver::Objective::Lowest,
+            };
+            let solution = solver::beam_search(&config, rocks, 100, &objective);
+            println!("height: {}", solution.height);
+            println!("jets: {}", solution.jets.iter().map(|jet| if *jet == Move::Left { '<' } else { '>' }).collect::<String>());
+            return;
+        },
+        _ => {},
+    }
+
+    let mut grid = Grid::new(&config);
+    let rock_launcher = RockLauncher::new(&config);
 
     run_tetris(rock_launcher, &mut grid, move_iter, moves.len(), 10000000);
     // println!("{:?}", grid);
@@ -162,29 +372,13 @@ fn run_tetris(mut rock_launcher: RockLauncher, grid: &mut Grid, mut move_iter: s
     for _ in 0..iterations {
         let mut rock = rock_launcher.launch(grid.max_height());
         // println!("rock: {:?}", rock);
-        grid.add_three_lines();
+        grid.add_lines_for(&rock);
 
         let mut can_go_down = true;
         while can_go_down {
-            match move_iter.next().unwrap() {
-                Move::Left => {
-                    if !grid.intersect(rock.left()) {
-                        rock.go_left();
-                    }
-                },
-                Move::Right => {
-                    if !(grid.intersect(rock.right())) {
-                        rock.go_right();
-                    }
-                }
-            }
+            grid.push(&mut rock, move_iter.next().unwrap());
             counter += 1;
-            can_go_down = !grid.intersect(rock.bottom());
-            if can_go_down {
-                rock.go_down();
-            } else {
-                grid.add_rock(&rock);
-            }
+            can_go_down = grid.fall(&mut rock);
         }
         // println!("{}", counter % nb_moves);
         if counter % nb_moves == 0 {
@@ -195,3 +389,49 @@ fn run_tetris(mut rock_launcher: RockLauncher, grid: &mut Grid, mut move_iter: s
     println!("{}", counter);
     println!("{:?}", heights);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{run_tetris, ChamberConfig, Grid, Move, RockLauncher, RockShape};
+
+    const JETS: &str = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>";
+
+    #[test]
+    fn parse_rock_shape() {
+        let shape = RockShape::from(".#.\n###\n.#.");
+
+        assert_eq!(shape.cells, vec![(0, 1), (1, 0), (1, 1), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn parse_chamber_config() {
+        let config = ChamberConfig::parse("width: 5\nleft: 1\nbottom: 2\n\n##\n\n#\n#").unwrap();
+
+        assert_eq!(config.width, 5);
+        assert_eq!(config.left_gap, 1);
+        assert_eq!(config.bottom_gap, 2);
+        assert_eq!(config.shapes, vec![RockShape { cells: vec![(0, 0), (0, 1)] }, RockShape { cells: vec![(0, 0), (1, 0)] }]);
+    }
+
+    #[test]
+    fn reject_invalid_chamber_config() {
+        assert_eq!(ChamberConfig::parse("height: 3"), Err("unknown key `height`, expected `width`, `left` or `bottom`".to_string()));
+        assert_eq!(ChamberConfig::parse("width: x"), Err("`x` is not a number in `width: x`".to_string()));
+        assert_eq!(ChamberConfig::parse("width: 5\nleft 1"), Err("expected `key: value`, found `left 1`".to_string()));
+        assert_eq!(ChamberConfig::parse("width: 0"), Err("the chamber cannot have a width of 0".to_string()));
+        assert_eq!(ChamberConfig::parse("width: 3"), Err("rock 1 is 4 wide and does not fit 2 from the left of a chamber 3 wide".to_string()));
+        assert_eq!(ChamberConfig::parse("##\n\n..."), Err("rock 2 has no cells".to_string()));
+        assert!(ChamberConfig::parse("width: 4\nleft: 0\n\n####").is_ok());
+    }
+
+    #[test]
+    fn default_config_matches_puzzle_example() {
+        let config = ChamberConfig::default();
+        let moves = JETS.chars().map(Move::from).collect::<Vec<Move>>();
+        let mut grid = Grid::new(&config);
+
+        run_tetris(RockLauncher::new(&config), &mut grid, moves.iter().cycle(), moves.len(), 2022);
+
+        assert_eq!(grid.max_height(), 3068);
+    }
+}
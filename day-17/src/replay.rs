@@ -0,0 +1,141 @@
+use std::{
+    io::{self, BufRead, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{Cell, ChamberConfig, Environment, Move};
+
+const VIEWPORT_HEIGHT: usize = 20;
+const FRAME_DELAY: Duration = Duration::from_millis(80);
+
+pub struct Replay<'a> {
+    environment: Environment,
+    moves: &'a [Move],
+    jet: usize,
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(config: &ChamberConfig, moves: &'a [Move]) -> Self {
+        Replay { environment: Environment::new(config), moves, jet: 0 }
+    }
+
+    pub fn step(&mut self) -> bool {
+        let settled = self.environment.step(&self.moves[self.jet % self.moves.len()]);
+        self.jet += 1;
+        settled
+    }
+
+    fn rock_number(&self) -> usize {
+        self.environment.rocks_settled + 1
+    }
+
+    // Plays jets until the given rock (1-based) is the falling one.
+    pub fn fast_forward(&mut self, rock_number: usize) {
+        while self.rock_number() < rock_number {
+            self.step();
+        }
+    }
+
+    pub fn next_jet(&self) -> char {
+        match self.moves[self.jet % self.moves.len()] {
+            Move::Left => '<',
+            Move::Right => '>',
+        }
+    }
+
+    // Draws the chamber as in the puzzle text, keeping the top of the falling rock in view.
+    pub fn render(&self, rows: usize) -> String {
+        let grid = &self.environment.grid;
+        let rock = &self.environment.rock;
+        let top = rock.cells.iter().map(|(y, _)| *y).max().unwrap().max(grid.max_height());
+        let bottom = (top + 1).saturating_sub(rows);
+        let mut lines = vec![format!(
+            "Rock {}, jet {} ({}), height {}",
+            self.rock_number(),
+            self.jet % self.moves.len(),
+            self.next_jet(),
+            grid.max_height()
+        )];
+        for y in (bottom..=top).rev() {
+            if y == 0 {
+                lines.push(format!("+{}+", "-".repeat(grid.width)));
+                continue;
+            }
+            let line = (0..grid.width + 2)
+                .map(|x| {
+                    if x == 0 || x == grid.width + 1 {
+                        '|'
+                    } else if rock.cells.contains(&(y, x)) {
+                        '@'
+                    } else if grid.cell(y, x) == Cell::Full {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+}
+
+// Commands: empty line steps one jet, `r` finishes the current rock, `p` plays until `N` rocks,
+// `f N` fast-forwards to rock N, `q` quits.
+pub fn run(config: &ChamberConfig, moves: &[Move]) {
+    let mut replay = Replay::new(config, moves);
+    println!("{}", replay.render(VIEWPORT_HEIGHT));
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let mut split = line.split_whitespace();
+        match (split.next(), split.next().map(|n| n.parse::<usize>())) {
+            (None, _) => {
+                replay.step();
+            },
+            (Some("r"), _) => while !replay.step() {},
+            (Some("f"), Some(Ok(rock_number))) => replay.fast_forward(rock_number),
+            (Some("p"), Some(Ok(rock_number))) => {
+                while replay.rock_number() < rock_number {
+                    replay.step();
+                    println!("\x1b[2J\x1b[H{}", replay.render(VIEWPORT_HEIGHT));
+                    io::stdout().flush().unwrap();
+                    thread::sleep(FRAME_DELAY);
+                }
+            },
+            (Some("q"), _) => break,
+            _ => {
+                println!("Unknown command: {}", line);
+                continue;
+            },
+        }
+        println!("{}", replay.render(VIEWPORT_HEIGHT));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{replay::Replay, ChamberConfig, Move};
+
+    #[test]
+    fn render_first_rock_after_one_jet() {
+        let moves = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>".chars().map(Move::from).collect::<Vec<Move>>();
+        let mut replay = Replay::new(&ChamberConfig::default(), &moves);
+        replay.step();
+
+        assert_eq!(replay.render(5), "Rock 1, jet 1 (>), height 0\n|...@@@@|\n|.......|\n|.......|\n+-------+");
+    }
+
+    #[test]
+    fn fast_forward_settles_previous_rocks() {
+        let moves = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>".chars().map(Move::from).collect::<Vec<Move>>();
+        let mut replay = Replay::new(&ChamberConfig::default(), &moves);
+        replay.fast_forward(3);
+
+        assert_eq!(
+            replay.render(10),
+            "Rock 3, jet 8 (>), height 4\n|....@..|\n|....@..|\n|..@@@..|\n|.......|\n|.......|\n|.......|\n|...#...|\n|..###..|\n|...#...|\n|..####.|"
+        );
+    }
+}
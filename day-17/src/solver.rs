@@ -0,0 +1,135 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{ChamberConfig, Environment, Move, Rock};
+
+pub enum Objective {
+    Lowest,
+    // Depth of each column below the top of the tower, as returned by `Environment::surface`.
+    Surface(Vec<usize>),
+}
+
+impl Objective {
+    // Lower is better.
+    fn score(&self, environment: &Environment) -> (usize, usize) {
+        let surface = environment.surface();
+        let height = environment.grid.max_height();
+        match self {
+            Objective::Lowest => (height, surface.iter().sum()),
+            Objective::Surface(target) => (surface.iter().zip(target).map(|(a, b)| a.abs_diff(*b)).sum(), height),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub jets: Vec<Move>,
+    pub height: usize,
+}
+
+// Every resting position the current rock can reach, with the shortest jet sequence leading to it.
+fn placements(environment: &Environment) -> Vec<Vec<Move>> {
+    let grid = &environment.grid;
+    let mut seen: HashSet<Rock> = HashSet::from([environment.rock.clone()]);
+    let mut resting: HashSet<Rock> = HashSet::new();
+    let mut queue = VecDeque::from([(environment.rock.clone(), vec![])]);
+    let mut result = vec![];
+    while let Some((rock, jets)) = queue.pop_front() {
+        for jet in [Move::Left, Move::Right] {
+            let mut next = rock.clone();
+            grid.push(&mut next, &jet);
+            let mut next_jets = jets.clone();
+            next_jets.push(jet);
+            if grid.intersect(next.bottom()) {
+                if resting.insert(next) {
+                    result.push(next_jets);
+                }
+            } else {
+                next.go_down();
+                if seen.insert(next.clone()) {
+                    queue.push_back((next, next_jets));
+                }
+            }
+        }
+    }
+    result
+}
+
+// Chooses the jets itself, keeping the `beam_width` best chambers after each rock. Chambers with the
+// same surface profile are considered equivalent.
+pub fn beam_search(config: &ChamberConfig, rocks: usize, beam_width: usize, objective: &Objective) -> Solution {
+    let mut beam = vec![(Environment::new(config), vec![])];
+    for _ in 0..rocks {
+        let mut candidates = vec![];
+        for (environment, jets) in &beam {
+            for placement in placements(environment) {
+                let mut next = environment.clone();
+                for jet in &placement {
+                    next.step(jet);
+                }
+                let mut next_jets: Vec<Move> = jets.clone();
+                next_jets.extend(placement);
+                candidates.push((next, next_jets));
+            }
+        }
+        candidates.sort_by_key(|(environment, _)| objective.score(environment));
+        let mut surfaces = HashSet::new();
+        beam = candidates
+            .into_iter()
+            .filter(|(environment, _)| surfaces.insert(environment.surface()))
+            .take(beam_width)
+            .collect();
+    }
+    let (environment, jets) = beam.into_iter().next().unwrap();
+    Solution { jets, height: environment.grid.max_height() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        solver::{beam_search, Objective},
+        ChamberConfig, Environment, Move,
+    };
+
+    #[test]
+    fn two_rocks_fit_side_by_side() {
+        let solution = beam_search(&ChamberConfig::default(), 2, 10, &Objective::Lowest);
+
+        assert_eq!(solution.height, 3);
+    }
+
+    #[test]
+    fn solution_replays_to_its_height() {
+        let config = ChamberConfig::default();
+        let solution = beam_search(&config, 20, 20, &Objective::Lowest);
+        let mut environment = Environment::new(&config);
+        for jet in &solution.jets {
+            environment.step(jet);
+        }
+
+        assert_eq!(environment.rocks_settled, 20);
+        assert_eq!(environment.grid.max_height(), solution.height);
+    }
+
+    #[test]
+    fn beats_the_puzzle_jets() {
+        let config = ChamberConfig::default();
+        let mut environment = Environment::new(&config);
+        let moves = ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>".chars().map(Move::from).collect::<Vec<Move>>();
+        let mut jets = moves.iter().cycle();
+        while environment.rocks_settled < 20 {
+            environment.step(jets.next().unwrap());
+        }
+
+        let solution = beam_search(&config, 20, 20, &Objective::Lowest);
+
+        assert!(solution.height < environment.grid.max_height());
+    }
+
+    #[test]
+    fn reaches_flat_target_surface() {
+        let config = ChamberConfig::parse("width: 4\nleft: 0\n\n####").unwrap();
+        let solution = beam_search(&config, 3, 5, &Objective::Surface(vec![0, 0, 0, 0]));
+
+        assert_eq!(solution.height, 3);
+    }
+}